@@ -0,0 +1,159 @@
+use crate::command::{Bins, FreqArgs};
+use crate::ordered::OrderedString;
+
+use std::collections::HashMap;
+
+use counter::Counter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Bin {
+    Under,
+    At(i64),
+    Over,
+}
+
+// bins further out than this are counted as under or overflow, so that bin
+// numbers and their bounds stay well within range
+const MAX_BIN: f64 = 1e9;
+
+pub struct Binner {
+    bins: Bins,
+    range: Option<(f64, f64)>,
+}
+
+fn parse(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+// print numbers without floating point noise like 0.30000000000000004
+fn fmt_num(v: f64) -> String {
+    let s = format!("{:.10}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { String::from("0") } else { s.to_string() }
+}
+
+// round up to 1, 2, or 5 times a power of ten
+fn nice(v: f64) -> f64 {
+    let mag = 10_f64.powf(v.log10().floor());
+    let norm = v / mag;
+    mag * if norm <= 1.0 {
+        1.0
+    } else if norm <= 2.0 {
+        2.0
+    } else if norm <= 5.0 {
+        5.0
+    } else {
+        10.0
+    }
+}
+
+impl Binner {
+    // automatic bin widths depend on the data, so it's needed up front
+    pub fn new(args: &FreqArgs, counter: &Counter<OrderedString>) -> Option<Self> {
+        let bins = match args.bins? {
+            Bins::Auto => {
+                let (n, low, high) = counter.iter()
+                    .filter_map(|(value, count)| parse(value).map(|v| (v, *count)))
+                    .filter(|(v, _)| args.bin_range.is_none_or(|(low, high)| *v >= low && *v < high))
+                    .fold((0, f64::INFINITY, f64::NEG_INFINITY), |(n, low, high), (v, count)| {
+                        (n + count, low.min(v), high.max(v))
+                    });
+
+                // Sturges' rule for the number of bins
+                let k = (n.max(1) as f64).log2().ceil() + 1.0;
+                if n == 0 || high <= low {
+                    Bins::Linear(1.0)
+                } else {
+                    Bins::Linear(nice((high - low) / k))
+                }
+            },
+            bins => bins,
+        };
+
+        Some(Binner { bins, range: args.bin_range })
+    }
+
+    fn bin(&self, v: f64) -> Bin {
+        if let Some((low, high)) = self.range {
+            if v < low {
+                return Bin::Under;
+            } else if v >= high {
+                return Bin::Over;
+            }
+        }
+
+        let k = match self.bins {
+            Bins::Linear(width) => (v / width).floor(),
+            Bins::Log(_) if v <= 0.0 => return Bin::Under,
+            Bins::Log(base) => {
+                // correct for rounding errors at exact powers
+                let k = v.log(base).floor();
+                if base.powf(k + 1.0) <= v {
+                    k + 1.0
+                } else if base.powf(k) > v {
+                    k - 1.0
+                } else {
+                    k
+                }
+            },
+            Bins::Auto => unreachable!("automatic bins are resolved on creation"),
+        };
+
+        if k < -MAX_BIN {
+            Bin::Under
+        } else if k > MAX_BIN {
+            Bin::Over
+        } else {
+            Bin::At(k as i64)
+        }
+    }
+
+    fn bounds(&self, k: i64) -> (f64, f64) {
+        let k = k as f64;
+        match self.bins {
+            Bins::Linear(width) => (k * width, (k + 1.0) * width),
+            Bins::Log(base) => (base.powf(k), base.powf(k + 1.0)),
+            Bins::Auto => unreachable!("automatic bins are resolved on creation"),
+        }
+    }
+
+    fn label(&self, bin: Bin) -> String {
+        match (bin, self.range) {
+            (Bin::Under, Some((low, _))) => format!("(-inf,{})", fmt_num(low)),
+            (Bin::Under, None) => match fmt_num(self.bounds(-MAX_BIN as i64).0).as_str() {
+                // non-positive values for log bins
+                "0" => String::from("(-inf,0]"),
+                low => format!("(-inf,{})", low),
+            },
+            (Bin::Over, Some((_, high))) => format!("[{},inf)", fmt_num(high)),
+            (Bin::Over, None) => format!("[{},inf)", fmt_num(self.bounds(MAX_BIN as i64).1)),
+            (Bin::At(k), range) => {
+                // the range may cut off the first and last bins
+                let (mut low, mut high) = self.bounds(k);
+                if let Some((range_low, range_high)) = range {
+                    low = low.max(range_low);
+                    high = high.min(range_high);
+                }
+                format!("[{},{})", fmt_num(low), fmt_num(high))
+            },
+        }
+    }
+
+    // replace values with their bins, ordered by position
+    pub fn apply(&self, counter: Counter<OrderedString>) -> Counter<OrderedString> {
+        let mut counts = HashMap::<Bin, usize>::new();
+        for (value, count) in counter.into_iter() {
+            if let Some(v) = parse(&value) {
+                *counts.entry(self.bin(v)).or_insert(0) += count;
+            }
+        }
+
+        let mut bins = counts.into_iter().collect::<Vec<_>>();
+        bins.sort_unstable();
+
+        bins.into_iter()
+            .enumerate()
+            .map(|(i, (bin, count))| (OrderedString::new(i, self.label(bin)), count))
+            .collect()
+    }
+}
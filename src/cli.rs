@@ -4,9 +4,153 @@
 // and the build script for documentation generation. It contains the
 // complete command-line interface specification using clap derive macros.
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::num::NonZeroUsize;
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Form {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Extract {
+    /// IPv4 or IPv6 addresses
+    Ip,
+    /// IPv4 addresses
+    Ipv4,
+    /// IPv6 addresses, in canonical compressed form
+    Ipv6,
+    /// Email addresses
+    Email,
+    /// URLs with a scheme, like `https://...`
+    Url,
+    /// Domain names
+    Domain,
+    /// UUIDs
+    Uuid,
+    /// Hex numbers with a `0x` prefix, or hashes and ids of at least 8 hex digits
+    Hex,
+    /// Integers and decimal numbers
+    Number,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tokens {
+    /// Words, ignoring punctuation
+    Words,
+    /// Characters other than whitespace
+    Chars,
+    /// User-perceived characters, such as a letter with combining accents
+    #[cfg(feature = "unicode")]
+    Graphemes,
+    /// Bytes, with anything other than printable ASCII shown as `\xNN`
+    Bytes,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DiffSort {
+    /// Largest change in either direction
+    Change,
+    /// Largest increase
+    Delta,
+    /// Largest ratio of current to baseline count
+    Ratio,
+    /// Largest increase in percent of the total
+    Share,
+    /// Most significant change, using `--test`
+    Score,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// Count the baseline the same way as the input
+    Input,
+    /// Read counts from previous output of freq or `uniq -c`
+    Freq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Test {
+    /// Log-likelihood ratio test
+    G2,
+    /// Pearson's chi-square test
+    Chi2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SampleBy {
+    /// Keep a random fraction of lines
+    Line,
+    /// Keep every occurrence of a fraction of values
+    Key,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rank {
+    /// Tied values share the best rank, then skip ahead (1, 2, 2, 4)
+    Competition,
+    /// Tied values share a rank, without gaps (1, 2, 2, 3)
+    Dense,
+    /// Every value gets its own rank, in output order (1, 2, 3, 4)
+    Ordinal,
+    /// Tied values share the average of their ranks (1, 2.5, 2.5, 4)
+    Fractional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PctOf {
+    /// Percent of the total of every value
+    Overall,
+    /// Percent of the total of values left after filtering
+    Filtered,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum Rollup {
+    Ipv4(u8),
+    Ipv6(u8),
+    Domain,
+    Path(usize),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    Bytes,
+    Natural,
+    Numeric,
+    Ip,
+    Length,
+    Nocase,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Count,
+    Value(Collation),
+    Order,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub desc: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum Bins {
+    Linear(f64),
+    Log(f64),
+    Auto,
+}
+
 #[derive(Debug, Parser)]
 #[command(name = env!("CARGO_PKG_NAME"))]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -21,18 +165,217 @@ pub struct FreqArgs {
 
     #[arg(
         short, long,
-        help = "Allow overwriting existing files with -o or --output",
-        long_help = "By default, when an output file is specified with `-o`, `freq` will not overwrite files that already exist. Pass `-f` to override this precaution.",
+        help = "Allow overwriting existing files with -o or --save-state",
+        long_help = "By default, when an output file is specified with `-o` or `--save-state`, `freq` will not overwrite files that already exist. Pass `-f` to override this precaution.",
     )]
     pub force: bool,
 
     #[cfg(feature = "_regex")]
     #[arg(
-        short = 'g', long, alias = "regexp", value_name = "REGEX",
-        help = "Match regular expression",
-        long_help = "Match regular expression. With no capture groups, this will act as a filter. With capture groups, the text within the capture groups is joined with tabs to become the value. Named capture groups are sorted lexically using the names as keys. Unnamed capture groups come after named capture groups.\n\nThe capture group named `n` is interpreted as the number of times a value appears. If used, you will also need to capture a value. This is useful to reprocess previous output of `freq`.",
+        short = 'g', long, alias = "regexp", value_name = "REGEX", conflicts_with_all = ["extract", "tokens", "ngram", "stopwords", "stopword_file", "merge"],
+        help = "Match regular expression, can be repeated",
+        long_help = "Match regular expression. With no capture groups, this will act as a filter. With capture groups, the text within the capture groups is joined with tabs to become the value. Named capture groups are sorted lexically using the names as keys. Unnamed capture groups come after named capture groups.\n\nThe capture group named `n` is interpreted as the number of times a value appears. If used, you will also need to capture a value. This is useful to reprocess previous output of `freq`.\n\nThis option can be given more than once, in which case each line is tried against the patterns in order and the first one to match is used.",
     )]
-    pub regex: Option<String>,
+    pub regex: Vec<String>,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        short = 'O', long, requires = "regex",
+        help = "Count every match in a line, not just the first",
+        long_help = "Count every non-overlapping match in a line, not just the first, similar to `grep -o`. If the regular expression has no capture groups, the matched text becomes the value rather than the entire line.",
+    )]
+    pub all_matches: bool,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        long, requires = "regex",
+        help = "Count a value for every pattern that matches a line",
+    )]
+    pub all_patterns: bool,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        long, requires = "regex",
+        help = "Prefix values with the label of the pattern that matched",
+        long_help = "Prefix values with the label of the pattern that matched, separated by a tab. Patterns are labeled with `--pattern-label`, or with their position, starting from 1.",
+    )]
+    pub label_patterns: bool,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        long, value_name = "LABEL", requires = "label_patterns",
+        help = "Label the patterns in order for --label-patterns, can be repeated",
+        long_help = "Label the patterns in order for `--label-patterns`, so the first LABEL goes with the first `-g`, and so on. This can be given more than once, and patterns past the last LABEL are labeled with their position.",
+    )]
+    pub pattern_label: Vec<String>,
+
+    #[arg(
+        long, value_name = "FILE",
+        help = "Save the counts to FILE for use with --load-state",
+        long_help = "Save the counts to FILE in a compact binary format, including the order values were first seen in, so they can be added to later runs with `--load-state`. This is written before any binning, sorting, or limits are applied.",
+    )]
+    pub save_state: Option<String>,
+
+    #[arg(
+        long, value_name = "FILE",
+        help = "Add counts saved by --save-state from FILE, can be repeated",
+        long_help = "Add counts saved with `--save-state` from FILE to the counts from the input, for example to build a weekly report from daily runs. This can be given more than once. If no input files are given, only the saved counts are used rather than reading from STDIN. With `--by-file`, each saved state gets its own column.",
+    )]
+    pub load_state: Vec<String>,
+
+    #[arg(
+        long, value_name = "BASELINE", conflicts_with_all = ["crosstab", "bins", "by_file", "unique"],
+        help = "Compare counts against BASELINE",
+        long_help = "Compare counts against BASELINE, which can be a file saved with `--save-state`, or input that is counted the same way as the rest of the input. Use `--diff-format freq` to read BASELINE as previous output of `freq` instead. For each value, the output shows the baseline count, the current count, the change, the ratio of current to baseline, the change in percent of the total, and whether the value is `new` or `gone`. With `--tsv` or `--csv`, the first row names the columns.",
+    )]
+    pub diff: Option<String>,
+
+    #[arg(
+        long, value_name = "FORMAT", default_value = "input", requires = "diff",
+        help = "How to read BASELINE, unless it was saved by --save-state",
+    )]
+    pub diff_format: DiffFormat,
+
+    #[arg(
+        long, value_name = "ORDER", default_value = "change", requires = "diff",
+        help = "Sort compared values by ORDER",
+    )]
+    pub diff_sort: DiffSort,
+
+    #[arg(
+        long, value_name = "TEST", requires = "diff",
+        help = "Score how significant each change is using TEST",
+        long_help = "Score how significant each change is using TEST, comparing each value's share of the baseline and current counts. This adds columns for the score and its p-value, and a `(total)` row testing whether the distribution as a whole has changed. Unlike the raw change, this accounts for how much variation is expected at each count. Sorting with `--diff-sort score` uses the G² test if no TEST is given.",
+    )]
+    pub test: Option<Test>,
+
+    #[arg(
+        short = 'M', long, conflicts_with_all = ["extract", "tokens", "ngram", "stopwords", "stopword_file", "sample", "skip_header"],
+        help = "Sum the counts from previous output of freq or `uniq -c`",
+        long_help = "Sum the counts from previous output of `freq` or `uniq -c`, such as results collected from several hosts. The default, `--tsv`, and `--csv` layouts are detected automatically for each input, including any extra columns. With `-n` or `--rank`, line numbers are told apart from counts using the pct column, and it's an error if they can't be.",
+    )]
+    pub merge: bool,
+
+    #[arg(
+        short = 'e', long, value_name = "TYPE",
+        help = "Count every token of TYPE found in each line",
+        long_help = "Count every token of TYPE found in each line, without needing a regular expression. IPv6 addresses are shown in their canonical compressed form, and domains, UUIDs, and hex numbers are lowercased. Ports after addresses and trailing punctuation after URLs are not included.",
+    )]
+    pub extract: Option<Extract>,
+
+    #[arg(
+        long, value_name = "MODE", conflicts_with = "extract",
+        help = "Split lines into tokens and count each one",
+        long_help = "Split lines into tokens and count each one, rather than counting whole lines. Words are split using Unicode word boundaries when the `unicode` feature is enabled, and at characters that aren't letters, numbers, or apostrophes otherwise. Combine with `-i` to count words case insensitively.",
+    )]
+    pub tokens: Option<Tokens>,
+
+    #[arg(
+        long, value_name = "N", conflicts_with = "extract",
+        help = "Count sliding sequences of N tokens [default tokens: words]",
+        long_help = "Count sliding sequences of N tokens within each line, rather than single tokens. Words are joined with a space, and other tokens are joined without a separator. If `--tokens` isn't given, lines are split into words.",
+    )]
+    pub ngram: Option<NonZeroUsize>,
+
+    #[arg(
+        long, conflicts_with = "extract",
+        help = "Skip common English words when splitting lines into tokens",
+        long_help = "Skip common English words, such as `the` and `of`, when splitting lines into tokens. Stopwords are matched case insensitively and are removed before n-grams are formed. If `--tokens` isn't given, lines are split into words.",
+    )]
+    pub stopwords: bool,
+
+    #[arg(
+        long, value_name = "FILE", conflicts_with = "extract",
+        help = "Skip tokens listed in FILE, can be repeated",
+        long_help = "Skip tokens listed in FILE, one per line, when splitting lines into tokens. Like `--stopwords`, these are matched case insensitively and are removed before n-grams are formed. This can be given more than once, and can be combined with `--stopwords`.",
+    )]
+    pub stopword_file: Vec<String>,
+
+    #[arg(
+        long, value_name = "SPEC", value_parser = parse_rollup,
+        help = "Collapse values to a prefix before counting, can be repeated",
+        long_help = "Collapse values to a prefix before counting. SPEC is one of `ipv4:N` to collapse IPv4 addresses to their /N network, `ipv6:N` to do the same for IPv6 addresses, `domain` to collapse domain names to their registrable domain using the public suffix list, or `path:N` to keep only the first N segments of URL and file paths, dropping any query string. This can be given more than once to handle different kinds of values. Each tab separated part of a value is collapsed separately, and parts that don't match are left unchanged.",
+    )]
+    pub rollup: Vec<Rollup>,
+
+    #[cfg(feature = "unicode")]
+    #[arg(
+        short = 'i', long,
+        help = "Count values case insensitively",
+        long_help = "Count values case insensitively, using Unicode case folding. Values are shown in their folded form unless `--keep-spelling` is used.",
+    )]
+    pub fold_case: bool,
+
+    #[cfg(feature = "unicode")]
+    #[arg(
+        long, requires = "fold_case",
+        help = "Show the most common spelling of case folded values",
+    )]
+    pub keep_spelling: bool,
+
+    #[cfg(feature = "unicode")]
+    #[arg(
+        long, value_name = "FORM",
+        help = "Apply Unicode normalization FORM to values",
+    )]
+    pub normalize: Option<Form>,
+
+    #[arg(
+        long,
+        help = "Trim whitespace from the start and end of values",
+        long_help = "Trim whitespace from the start and end of values. Each tab separated part of a value, such as from separate capture groups, is trimmed separately.",
+    )]
+    pub trim: bool,
+
+    #[arg(
+        long,
+        help = "Collapse runs of whitespace within values to a single space",
+    )]
+    pub squeeze: bool,
+
+    #[arg(
+        long,
+        help = "Skip empty values",
+        long_help = "Skip empty values, after any other normalization. Values made up of several tab separated parts are only skipped if every part is empty.",
+    )]
+    pub skip_empty: bool,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        short = 'v', long, requires = "regex", conflicts_with_all = ["replace", "all_matches", "label_patterns"],
+        help = "Count lines that don't match any regular expression",
+    )]
+    pub invert_match: bool,
+
+    #[arg(
+        long, value_name = "FILE",
+        help = "Skip values listed in FILE, can be repeated",
+        long_help = "Skip values listed in FILE, one per line. This is applied after values are extracted and normalized, and can be given more than once.",
+    )]
+    pub exclude_file: Vec<String>,
+
+    #[arg(
+        long, value_name = "FILE",
+        help = "Only count values listed in FILE, can be repeated",
+        long_help = "Only count values listed in FILE, one per line. This is applied after values are extracted and normalized, and can be given more than once. Values listed in an `--exclude-file` are still skipped.",
+    )]
+    pub include_file: Vec<String>,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        long,
+        help = "Treat lines of value list files as regular expressions",
+        long_help = "Treat lines of `--exclude-file` and `--include-file` files as regular expressions, which match if they're found anywhere in a value, rather than exact values.",
+    )]
+    pub list_regex: bool,
+
+    #[cfg(feature = "_regex")]
+    #[arg(
+        short = 'R', long, value_name = "TEMPLATE", requires = "regex",
+        help = "Build values from capture groups using TEMPLATE",
+        long_help = "Build values from the capture groups of `-g` using TEMPLATE, rather than joining them with tabs. Groups are referenced as `$name` or `$1`, or as `${name}` to separate them from following text, and `$0` is the entire match. Use `$$` for a literal `$`.\n\nTransforms can be applied to a group with `${name|transform}`, and chained. Available transforms are `lower`, `upper`, `trim`, and `truncate:N`.\n\nThe `n` and `t` capture groups keep their special meaning.",
+    )]
+    pub replace: Option<String>,
 
     #[arg(
         short, long, value_parser = 0..=9, default_value = "3", value_name = "N",
@@ -47,6 +390,12 @@ pub struct FreqArgs {
     )]
     pub limit: Option<usize>,
 
+    #[arg(
+        long, requires = "limit",
+        help = "Include values tied with the last one in the limit",
+    )]
+    pub with_ties: bool,
+
     #[arg(
         short, long, value_name = "N",
         help = "Limit output to values seen at least N times",
@@ -59,6 +408,62 @@ pub struct FreqArgs {
     )]
     pub max: Option<NonZeroUsize>,
 
+    #[arg(
+        long, value_name = "P", value_parser = parse_pct,
+        help = "Limit output to values that are at least P percent of the total",
+        long_help = "Limit output to values that are at least P percent of the total. The comparison uses the percentage as shown, rounded to the number of digits set by `-d`.",
+    )]
+    pub min_pct: Option<(u64, u32)>,
+
+    #[arg(
+        long, value_name = "P", value_parser = parse_pct,
+        help = "Stop output once the CDF reaches P percent",
+        long_help = "Stop output once the CDF reaches P percent, such as the values that make up 90% of the total. The value that reaches P is included. The comparison uses the CDF as shown, rounded to the number of digits set by `-d`.",
+    )]
+    pub until_cdf: Option<(u64, u32)>,
+
+    #[arg(
+        long, conflicts_with_all = ["crosstab", "diff"],
+        help = "Filter with --min and --max before numbering values",
+        long_help = "Filter with `--min` and `--max` before numbering values, so that `-n` has no gaps, `-l` counts only the values shown, and the running sum and CDF only include them.",
+    )]
+    pub filter_first: bool,
+
+    #[arg(
+        long, value_name = "TOTAL", default_value = "overall", requires = "filter_first",
+        help = "Total to compute percentages against with --filter-first",
+    )]
+    pub pct_of: PctOf,
+
+    #[arg(
+        long, conflicts_with_all = ["crosstab", "diff", "unique"],
+        help = "Add an (other) row for values hidden by limits",
+        long_help = "Add an (other) row after the table with the combined count and percentage of every value hidden by `-l`, `--min`, `--max`, `--min-pct` or `--until-cdf`, and how many distinct values that is. The row is left out if nothing was hidden.",
+    )]
+    pub other: bool,
+
+    #[arg(
+        long, conflicts_with_all = ["crosstab", "diff", "unique"],
+        help = "Add a (total) row with the grand total",
+    )]
+    pub total: bool,
+
+    #[arg(
+        long, value_name = "SPEC", value_parser = parse_sort_key, value_delimiter = ',',
+        conflicts_with_all = ["insertion", "lexigraphic", "unstable", "no_freq_sort", "reverse"],
+        help = "Sort by a list of keys, such as count:desc,value:natural",
+        long_help = "Sort by a comma separated list of keys, each compared in turn when the ones before it are equal. A key is `count`, `value` or `order` (when the value was first seen), optionally followed by `:asc` or `:desc`. Counts sort in descending order by default, and the others in ascending order. Values can also be followed by a collation: `bytes` [default], `natural` to compare runs of digits as numbers (`file2` before `file10`), `numeric` to compare values as numbers, `ip` to compare IP addresses and networks, `length` to compare the number of characters, or `nocase` to ignore case. Values that aren't numbers or IP addresses sort after those that are. Replaces `-I`, `-L`, `-U`, `-F` and `-r`, which are the same as `count,order`, `count,value`, `count`, `order` and `count:asc,order`.",
+    )]
+    pub sort: Option<Vec<SortKey>>,
+
+    #[arg(
+        long, value_name = "SPEC", value_parser = parse_sort_key, value_delimiter = ',',
+        conflicts_with_all = ["crosstab", "diff"],
+        help = "Reorder the values shown by SPEC, after picking them by frequency",
+        long_help = "Reorder the values shown by SPEC, after picking them by frequency. Values are first sorted and filtered as usual, with `-l`, `--min`, `--max` and the other limits, and then only the values that made the cut are sorted again, such as with `-l 20 --order-by value:numeric` for the 20 most common values in numeric order. SPEC is the same as for `--sort`, and ties keep their order by frequency. Line numbers and ranks still reflect frequency, but the running sum and CDF follow the new order.",
+    )]
+    pub order_by: Option<Vec<SortKey>>,
+
     #[arg(
         short = 'I', long, conflicts_with = "lexigraphic",
         help = "Sort values with same frequency by original order [default]",
@@ -108,6 +513,13 @@ pub struct FreqArgs {
     )]
     pub number: bool,
 
+    #[arg(
+        long, value_name = "MODE",
+        help = "Number values by rank of their count, implies --number",
+        long_help = "Number values by rank of their count, implies `--number`. `--rank ordinal` is the same as `-n`, which numbers values in the order they're shown.",
+    )]
+    pub rank: Option<Rank>,
+
     #[arg(
         short, long,
         help = "Include running sum totals",
@@ -126,6 +538,119 @@ pub struct FreqArgs {
     )]
     pub no_cdf: bool,
 
+    #[arg(
+        long, conflicts_with = "no_pct",
+        help = "Include confidence intervals for percentages",
+        long_help = "Include columns with the lower and upper bounds of a Wilson score confidence interval for each percentage, which shows how uncertain a share is when there's little data. The number of digits is set by `-d`.",
+    )]
+    pub ci: bool,
+
+    #[arg(
+        long, value_name = "PCT", default_value = "95", value_parser = parse_level, requires = "ci",
+        help = "Confidence level for --ci, in percent",
+    )]
+    pub ci_level: f64,
+
+    #[arg(
+        long, conflicts_with_all = ["crosstab", "diff", "unique"],
+        help = "Print summary statistics after the table",
+        long_help = "Print summary statistics after the table: the total and distinct counts, the number of values seen once, Shannon entropy in bits and as a fraction of the maximum, the Gini coefficient, an estimated Zipf exponent, the share of the total covered by the top values, and the median and mean count per value. The top values are the `-l` most common, or 10 if there's no limit. Limits and `--min`/`--max` don't otherwise affect the statistics.",
+    )]
+    pub stats: bool,
+
+    #[arg(
+        long, conflicts_with_all = ["crosstab", "diff", "unique"],
+        help = "Print summary statistics instead of the table",
+    )]
+    pub stats_only: bool,
+
+    #[arg(
+        short, long,
+        help = "Include a count column for each input file",
+        long_help = "Include a count column for each input file, labeled with the name of the file, in addition to the overall count. Sorting and limits apply to the overall count. A header row naming the columns is printed first, and in the default layout columns are widened to fit their labels.",
+    )]
+    pub by_file: bool,
+
+    #[arg(
+        short = 'X', long, conflicts_with_all = ["unique", "by_file"],
+        help = "Output a crosstab of values split at the first tab",
+        long_help = "Output a crosstab (pivot table) instead of a list. Each value is split at its first tab into a row key and a column key, so two capture groups with `-g` or two tab separated fields will work. Rows and columns are sorted like a list would be, and `-l` limits the number of rows. Totals for each row and column are included, and count hidden rows and columns.",
+    )]
+    pub crosstab: bool,
+
+    #[arg(
+        long, value_name = "M", requires = "crosstab",
+        help = "Limit crosstab output to top M columns",
+    )]
+    pub columns: Option<usize>,
+
+    #[arg(
+        long, requires = "crosstab",
+        help = "Show crosstab cells as percent of row total",
+    )]
+    pub row_pct: bool,
+
+    #[cfg(feature = "time")]
+    #[arg(
+        long, value_name = "N",
+        conflicts_with_all = [
+            "crosstab", "bins", "diff", "by_file", "stats", "stats_only", "other", "total", "number", "rank",
+            "unique", "sum", "ci", "min_pct", "until_cdf", "filter_first", "with_ties", "order_by",
+        ],
+        help = "Count values over time using timestamps from field N",
+        long_help = "Count values over time, using a timestamp taken from whitespace separated field N of each line. Text enclosed in square brackets or double quotes is treated as a single field. Without `-g`, the rest of the line becomes the value. Lines without a valid timestamp are skipped.\n\nTimestamps can also be captured with a regular expression group named `t`.\n\nThe output is a table with a row for each time bucket and a column for each of the top values, which `-l` limits.",
+    )]
+    pub time_field: Option<NonZeroUsize>,
+
+    #[cfg(feature = "time")]
+    #[arg(
+        long, value_name = "FORMAT",
+        help = "Parse timestamps using strftime FORMAT",
+        long_help = "Parse timestamps using a strftime style FORMAT, such as `%d/%b/%Y:%H:%M:%S %z`. Timestamps without an offset are assumed to be UTC. By default, RFC 3339, Apache/nginx log timestamps, and epoch seconds or milliseconds from 1973 on are recognized.",
+    )]
+    pub time_format: Option<String>,
+
+    #[cfg(feature = "time")]
+    #[arg(
+        long, value_name = "DURATION", default_value = "1h", value_parser = parse_duration,
+        help = "Width of time buckets",
+        long_help = "Width of time buckets, as a number followed by a unit of `s`, `m`, `h`, `d`, or `w`. Empty buckets between the first and last timestamp are filled in, unless there would be more than 100,000 of them.",
+    )]
+    pub bucket: i64,
+
+    #[arg(
+        long, value_name = "RATE", value_parser = parse_rate,
+        help = "Only count a fraction of the input, like 0.01 or 1%",
+        long_help = "Only count a fraction of the input, given as a number from 0 to 1 or a percentage. Lines that aren't sampled are skipped before any parsing. Counts are scaled up to estimate the counts for the entire input, and the percent column is followed by a 95% margin of error.",
+    )]
+    pub sample: Option<f64>,
+
+    #[arg(
+        long, value_name = "N", requires = "sample",
+        help = "Seed for sampling, to make it repeatable [default: random]",
+    )]
+    pub seed: Option<u64>,
+
+    #[arg(
+        long, value_name = "MODE", default_value = "line", requires = "sample",
+        help = "Sample by line or by value",
+        long_help = "Sample by line or by value. Sampling by line keeps a random fraction of lines. Sampling by value keeps every occurrence of a fraction of values chosen by a hash of the value, so their counts are exact, and percentages are estimated relative to the scaled up total.",
+    )]
+    pub sample_by: SampleBy,
+
+    #[arg(
+        long, value_name = "SPEC", value_parser = parse_bins, conflicts_with = "crosstab",
+        help = "Count numeric values in bins: linear:WIDTH, log:BASE, or auto",
+        long_help = "Count numeric values in bins rather than counting each exact value. With `linear:WIDTH`, bins are WIDTH apart, starting from zero. With `log:BASE`, each bin starts at a power of BASE. With `auto`, a linear bin width is chosen based on the range and number of values. Output is in bin order rather than by frequency. Values that aren't numbers are skipped.",
+    )]
+    pub bins: Option<Bins>,
+
+    #[arg(
+        long, value_name = "LOW:HIGH", value_parser = parse_range, requires = "bins",
+        help = "Count values outside LOW:HIGH in underflow and overflow bins",
+    )]
+    pub bin_range: Option<(f64, f64)>,
+
     #[arg(
         short, long, conflicts_with = "csv",
         help = "Tab delimited output",
@@ -156,3 +681,138 @@ pub struct FreqArgs {
     #[arg(last = true, allow_hyphen_values = true, hide = true)]
     pub files_raw: Vec<String>,
 }
+
+// parse a duration like `90s`, `5m`, `1h`, or `7d` into seconds
+#[allow(dead_code)]
+fn parse_duration(s: &str) -> Result<i64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+
+    let n: i64 = n.parse().map_err(|_| format!("invalid duration `{}`", s))?;
+    let scale = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit `{}`, expected one of s, m, h, d, w", unit)),
+    };
+
+    match n.checked_mul(scale) {
+        Some(0) => Err(String::from("duration must be greater than zero")),
+        Some(width) => Ok(width),
+        None => Err(format!("duration `{}` is too large", s)),
+    }
+}
+
+#[allow(dead_code)]
+fn parse_bins(s: &str) -> Result<Bins, String> {
+    let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+    let arg = || arg.parse::<f64>().ok().filter(|v| v.is_finite() && *v > 0.0);
+
+    match kind {
+        "linear" => arg().map(Bins::Linear).ok_or_else(|| String::from("width must be a positive number")),
+        "log" => arg().filter(|v| *v > 1.0).map(Bins::Log).ok_or_else(|| String::from("base must be a number greater than 1")),
+        "auto" if s == "auto" => Ok(Bins::Auto),
+        _ => Err(String::from("expected one of linear:WIDTH, log:BASE, or auto")),
+    }
+}
+
+#[allow(dead_code)]
+fn parse_rollup(s: &str) -> Result<Rollup, String> {
+    let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+    let arg = arg.strip_prefix('/').unwrap_or(arg);
+
+    match kind {
+        "ipv4" => arg.parse().ok().filter(|n| *n <= 32).map(Rollup::Ipv4)
+            .ok_or_else(|| String::from("prefix length must be from 0 to 32")),
+        "ipv6" => arg.parse().ok().filter(|n| *n <= 128).map(Rollup::Ipv6)
+            .ok_or_else(|| String::from("prefix length must be from 0 to 128")),
+        #[cfg(feature = "psl")]
+        "domain" if s == "domain" => Ok(Rollup::Domain),
+        #[cfg(not(feature = "psl"))]
+        "domain" => Err(String::from("domain rollup requires the `psl` feature")),
+        "path" => arg.parse().ok().filter(|n| *n > 0).map(Rollup::Path)
+            .ok_or_else(|| String::from("number of segments must be a positive integer")),
+        _ => Err(String::from("expected one of ipv4:N, ipv6:N, domain, or path:N")),
+    }
+}
+
+#[allow(dead_code)]
+fn parse_sort_key(s: &str) -> Result<SortKey, String> {
+    let mut parts = s.split(':');
+    let mut field = match parts.next() {
+        Some("count") => SortField::Count,
+        Some("value") => SortField::Value(Collation::Bytes),
+        Some("order") => SortField::Order,
+        _ => return Err(String::from("expected a key of count, value, or order")),
+    };
+    let mut desc = field == SortField::Count;
+
+    for part in parts {
+        field = match (field, part) {
+            (_, "asc") => { desc = false; field },
+            (_, "desc") => { desc = true; field },
+            (SortField::Value(_), "bytes") => SortField::Value(Collation::Bytes),
+            (SortField::Value(_), "natural") => SortField::Value(Collation::Natural),
+            (SortField::Value(_), "numeric") => SortField::Value(Collation::Numeric),
+            (SortField::Value(_), "ip") => SortField::Value(Collation::Ip),
+            (SortField::Value(_), "length") => SortField::Value(Collation::Length),
+            (SortField::Value(_), "nocase") => SortField::Value(Collation::Nocase),
+            (SortField::Value(_), _) => return Err(format!("unknown sort option `{}`, expected asc, desc, bytes, natural, numeric, ip, length, or nocase", part)),
+            _ => return Err(format!("unknown sort option `{}`, expected asc or desc", part)),
+        };
+    }
+
+    Ok(SortKey { field, desc })
+}
+
+#[allow(dead_code)]
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate = match s.strip_suffix('%') {
+        Some(pct) => pct.parse::<f64>().map(|v| v / 100.0),
+        None => s.parse::<f64>(),
+    };
+
+    rate.ok()
+        .filter(|v| *v > 0.0 && *v <= 1.0)
+        .ok_or_else(|| String::from("expected a number greater than 0 and at most 1, or a percentage"))
+}
+
+#[allow(dead_code)]
+fn parse_level(s: &str) -> Result<f64, String> {
+    s.trim_end_matches('%').parse::<f64>().ok()
+        .filter(|v| *v > 0.0 && *v < 100.0)
+        .ok_or_else(|| String::from("expected a percentage greater than 0 and less than 100"))
+}
+
+// parse a percentage exactly, as digits and the number of decimal places
+#[allow(dead_code)]
+fn parse_pct(s: &str) -> Result<(u64, u32), String> {
+    let error = || String::from("expected a percentage from 0 to 100");
+    let s = s.trim_end_matches('%');
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+
+    if whole.is_empty() && frac.is_empty() || !whole.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) || frac.len() > 9 {
+        return Err(error());
+    }
+
+    let digits = format!("{}{}", whole, frac).parse::<u64>().map_err(|_| error())?;
+    let places = frac.len() as u32;
+    if digits > 100 * 10_u64.pow(places) {
+        return Err(error());
+    }
+
+    Ok((digits, places))
+}
+
+#[allow(dead_code)]
+fn parse_range(s: &str) -> Result<(f64, f64), String> {
+    let parse = |v: &str| v.parse::<f64>().ok().filter(|v| v.is_finite());
+
+    match s.split_once(':').map(|(low, high)| (parse(low), parse(high))) {
+        Some((Some(low), Some(high))) if low < high => Ok((low, high)),
+        Some((Some(_), Some(_))) => Err(String::from("LOW must be less than HIGH")),
+        _ => Err(String::from("expected two numbers, like 0:100")),
+    }
+}
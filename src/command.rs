@@ -6,15 +6,53 @@ use crate::egg::egg;
 
 use crate::ordered::OrderedString;
 
+use crate::crosstab::Crosstab;
+
+use crate::output::{csv_escape, Layout};
+
+use crate::bins::Binner;
+
+use crate::normalize::Normalizer;
+
+use crate::filter::KeyFilter;
+
+use crate::extract::extract;
+
+use crate::tokens::Tokenizer;
+
+use crate::sample::Sampler;
+
+use crate::merge;
+
+use crate::state;
+
+use crate::diff::Diff;
+
+use crate::stats::{normal_quantile, wilson, Summary};
+
+use crate::sort;
+
+use crate::rollup::Rollups;
+
+#[cfg(feature = "_regex")]
+use crate::template::{Ref, Template};
+
+#[cfg(feature = "time")]
+use crate::time::{TimeBucket, format_bucket, split_field};
+
 use crate::build_features::*;
 
 // stdlib
+use std::cell::Cell;
 use std::cmp::max;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
-use std::io::{self, Write, LineWriter, BufRead};
+use std::io::{self, Write, LineWriter, BufRead, Cursor};
 use std::mem::take;
 use std::num::NonZeroI32;
+#[cfg(feature = "time")]
+use std::num::NonZeroUsize;
 
 // packages
 use clap::{Command, FromArgMatches};
@@ -31,7 +69,9 @@ use fancy_regex::{Regex, Captures};
 mod cli {
     include!("cli.rs");
 }
-pub use cli::FreqArgs;
+pub use cli::{Bins, Collation, DiffFormat, DiffSort, Extract, FreqArgs, PctOf, Rank, Rollup, SampleBy, SortField, SortKey, Test, Tokens};
+#[cfg(feature = "unicode")]
+pub use cli::Form;
 
 #[cfg(all(feature = "regex-basic", not(feature = "regex-fancy")))]
 #[inline]
@@ -45,11 +85,42 @@ fn re_captures<'a>(re: &'a Regex, s: &'a str) -> Option<Captures<'a>> {
     re.captures(s).unwrap_or(None)
 }
 
+#[cfg(all(feature = "regex-basic", not(feature = "regex-fancy")))]
+#[inline]
+fn re_is_match(re: &Regex, s: &str) -> bool {
+    re.is_match(s)
+}
+
+#[cfg(feature = "regex-fancy")]
+#[inline]
+fn re_is_match(re: &Regex, s: &str) -> bool {
+    re.is_match(s).unwrap_or(false)
+}
+
+#[cfg(all(feature = "regex-basic", not(feature = "regex-fancy")))]
+#[inline]
+fn re_captures_iter<'a>(re: &'a Regex, s: &'a str) -> impl Iterator<Item = Captures<'a>> {
+    re.captures_iter(s)
+}
+
+#[cfg(feature = "regex-fancy")]
+#[inline]
+fn re_captures_iter<'a>(re: &'a Regex, s: &'a str) -> impl Iterator<Item = Captures<'a>> {
+    re.captures_iter(s).map_while(Result::ok)
+}
+
 type FnApplyRe<'a> = Box<dyn Fn(usize, &str) -> Option<(OrderedString, usize)> + 'a>;
+#[cfg(feature = "_regex")]
+type FnApplyAll<'a> = Box<dyn Fn(usize, &str) -> Vec<(OrderedString, usize)> + 'a>;
 
 #[cfg(feature = "_regex")]
-//fn mk_apply_re(re: &Regex) -> Result<Box<dyn Fn(usize, &str) -> Option<(OrderedString, usize)> + '_>, FatalError> {
-fn mk_apply_re(re: &Regex) -> Result<FnApplyRe<'_>, FatalError> {
+type FnItem<'a> = Box<dyn Fn(&Captures) -> String + 'a>;
+
+// work out how to build a value from the captures of a regex, returns whether
+// there's a count group, whether there's nothing to build a value from, and a
+// closure to build it
+#[cfg(feature = "_regex")]
+fn mk_item<'a>(re: &'a Regex, template: Option<&'a Template>) -> Result<(bool, bool, FnItem<'a>), FatalError> {
     use std::collections::HashSet;
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
     enum Group {
@@ -57,6 +128,11 @@ fn mk_apply_re(re: &Regex) -> Result<FnApplyRe<'_>, FatalError> {
         Number(usize),
     }
 
+    let error = |msg: String| FatalError::ClapUnfmt(
+        NonZeroI32::new(1).unwrap(),
+        clap::error::Error::raw(clap::error::ErrorKind::ValueValidation, msg),
+    );
+
     let mut data = re.capture_names()
         .enumerate()
         .skip(1)
@@ -71,37 +147,89 @@ fn mk_apply_re(re: &Regex) -> Result<FnApplyRe<'_>, FatalError> {
     let mut list = data.into_iter().collect::<Vec<_>>();
     list.sort();
 
-    Ok(if has_n {
-        if list.is_empty() {
-            return Err(FatalError::ClapUnfmt(
-                NonZeroI32::new(1).unwrap(),
-                clap::error::Error::raw(
-                    clap::error::ErrorKind::ValueValidation,
-                    format!("Regex `{}` captures a count without a value", re),
-                )
-            ));
+    // a captured timestamp goes first so that it can be split off later
+    #[cfg(feature = "time")]
+    if let Some(k) = list.iter().position(|v| *v == Group::Name("t".into())) {
+        let t = list.remove(k);
+        list.insert(0, t);
+    }
+
+    #[cfg(feature = "time")]
+    let has_t = list.first() == Some(&Group::Name("t".into()));
+    #[cfg(not(feature = "time"))]
+    let has_t = false;
+
+    // with no groups or template, the regex just acts as a filter
+    let whole_line = list.is_empty() && template.is_none();
+
+    if has_n && whole_line {
+        return Err(error(format!("Regex `{}` captures a count without a value", re)));
+    }
+
+    // build the value from the captured parts
+    let item: FnItem = if let Some(template) = template {
+        let names = re.capture_names().flatten().collect::<HashSet<_>>();
+        for group in template.refs() {
+            let valid = match group {
+                Ref::Name(name) => names.contains(name.as_str()),
+                Ref::Number(num) => *num < re.captures_len(),
+            };
+
+            if !valid {
+                return Err(error(format!("Regex `{}` has no capture group for `{}` in the template", re, group)));
+            }
         }
 
+        Box::new(move |captures| {
+            let value = template.expand(|group| match group {
+                Ref::Name(name) => captures.name(name),
+                Ref::Number(num) => captures.get(*num),
+            }.map(|v| v.as_str()));
+
+            if has_t {
+                let t = captures.name("t").map_or("", |v| v.as_str());
+                format!("{}\t{}", t, value)
+            } else {
+                value
+            }
+        })
+    } else {
+        Box::new(move |captures| list.iter().map(|v| match v {
+                Group::Name(name) => captures.name(name),
+                Group::Number(num) => captures.get(*num),
+            })
+            .map(|v| v.map_or_else(|| "", |v| v.as_str()).to_string())
+            .collect::<Vec<_>>().join("\t"))
+    };
+
+    Ok((has_n, whole_line, item))
+}
+
+#[cfg(feature = "_regex")]
+fn parse_n(captures: &Captures) -> usize {
+    captures.name("n")
+        .expect("no group n")
+        .as_str()
+        .parse()
+        .expect("group n doesn't contain a number")
+}
+
+#[cfg(feature = "_regex")]
+//fn mk_apply_re(re: &Regex) -> Result<Box<dyn Fn(usize, &str) -> Option<(OrderedString, usize)> + '_>, FatalError> {
+fn mk_apply_re<'a>(re: &'a Regex, template: Option<&'a Template>) -> Result<FnApplyRe<'a>, FatalError> {
+    let (has_n, whole_line, item) = mk_item(re, template)?;
+
+    Ok(if has_n {
         // return matched parts with count
         Box::new(move |i: usize, s: &str| {
             if let Some(captures) = re_captures(re, s) {
-                let n: usize = captures.name("n")
-                    .expect("no group n")
-                    .as_str()
-                    .parse()
-                    .expect("group n doesn't contain a number");
-                let item = list.iter().map(|v| match v {
-                        Group::Name(name) => captures.name(name),
-                        Group::Number(num) => captures.get(*num),
-                    })
-                    .map(|v| v.map_or_else(|| "", |v| v.as_str()).to_string())
-                    .collect::<Vec<_>>().join("\t");
-                Some((OrderedString::new(i, item), n))
+                let n = parse_n(&captures);
+                Some((OrderedString::new(i, item(&captures)), n))
             } else {
                 None
             }
         })
-    } else if list.is_empty() {
+    } else if whole_line {
         // return entire matched line
         Box::new(move |i: usize, s: &str| {
             if re_captures(re, s).is_some() {
@@ -113,21 +241,55 @@ fn mk_apply_re(re: &Regex) -> Result<FnApplyRe<'_>, FatalError> {
     } else {
         // return matched parts
         Box::new(move |i: usize, s: &str| {
-            if let Some(captures) = re_captures(re, s) {
-                let item = list.iter().map(|v| match v {
-                        Group::Name(name) => captures.name(name),
-                        Group::Number(num) => captures.get(*num),
-                    })
-                    .map(|v| v.map_or_else(|| "", |v| v.as_str()).to_string())
-                    .collect::<Vec<_>>().join("\t");
-                Some((OrderedString::new(i, item), 1usize))
-            } else {
-                None
-            }
+            re_captures(re, s).map(|captures| (OrderedString::new(i, item(&captures)), 1usize))
         })
     })
 }
 
+// like `mk_apply_re`, but for every match in the line rather than the first,
+// using the matched text as the value if there's nothing else to build it from
+#[cfg(feature = "_regex")]
+fn mk_apply_re_all<'a>(re: &'a Regex, template: Option<&'a Template>) -> Result<FnApplyAll<'a>, FatalError> {
+    let (has_n, whole_line, item) = mk_item(re, template)?;
+
+    Ok(Box::new(move |i: usize, s: &str| {
+        re_captures_iter(re, s)
+            // like `grep -o`, empty matches aren't counted
+            .filter(|captures| captures.get(0).is_some_and(|m| !m.as_str().is_empty()))
+            .map(|captures| {
+                let n = if has_n { parse_n(&captures) } else { 1 };
+                let value = if whole_line {
+                    captures.get(0).map_or("", |v| v.as_str()).to_string()
+                } else {
+                    item(&captures)
+                };
+                (OrderedString::new(i, value), n)
+            })
+            .collect()
+    }))
+}
+
+// prefix values with the start of the time bucket they fall in, taking the
+// timestamp from a field of the line or the first tab separated part
+#[cfg(feature = "time")]
+fn mk_apply_time<'a, F>(f: F, bucket: TimeBucket, field: Option<NonZeroUsize>) -> FnApplyRe<'a>
+where
+    F: Fn(usize, &str) -> Option<(OrderedString, usize)> + 'a,
+{
+    Box::new(move |i: usize, s: &str| time_prefix(&bucket, field, i, s, f(i, s)?))
+}
+
+#[cfg(feature = "time")]
+fn time_prefix(bucket: &TimeBucket, field: Option<NonZeroUsize>, i: usize, s: &str, item: (OrderedString, usize)) -> Option<(OrderedString, usize)> {
+    let (value, n) = item;
+    let (t, rest) = match field {
+        Some(field) => (split_field(s, field.get())?.0, value.as_ref()),
+        None => value.split_once('\t').unwrap_or((value.as_ref(), "")),
+    };
+    let t = bucket.bucket(t)?;
+    Some((OrderedString::new(i, format!("{}\t{}", t, rest)), n))
+}
+
 fn n_width(n: usize) -> usize {
     match n {
         0 => 1,
@@ -162,31 +324,133 @@ fn mk_fmt_int(digits: usize, lpad: bool) -> Box<dyn Fn(usize) -> String> {
     }
 }
 
-fn mk_idx(digits: usize, lpad: bool) -> Box<dyn Fn(usize, usize, usize, usize) -> String> {
+// rows either show a single value, or combine several of them
+#[derive(Clone, Copy, PartialEq)]
+enum RowKind {
+    Value,
+    Other,
+    Total,
+}
+
+// values available to the output columns when formatting a row
+struct Row<'a> {
+    kind: RowKind,
+    index: usize,
+    count: usize,
+    sum: usize,
+    total: usize,
+    value: &'a OrderedString,
+}
+
+type FnPart<'a> = Box<dyn Fn(&Row) -> String + 'a>;
+
+fn mk_idx<'a>(digits: usize, lpad: bool) -> FnPart<'a> {
     let f = mk_fmt_int(digits, lpad);
-    Box::new(move |i, _c, _r, _t| f(i))
+    Box::new(move |r| match r.kind {
+        RowKind::Value => f(r.index),
+        // combined rows don't have a place in the order
+        _ if lpad => format!("{:digits$}", ""),
+        _ => String::new(),
+    })
 }
 
-fn mk_cnt(digits: usize, lpad: bool) -> Box<dyn Fn(usize, usize, usize, usize) -> String> {
+// rank of the row's count among all counts, ties share a rank
+fn mk_rank<'a>(rank: Rank, counts: Vec<usize>, digits: usize, lpad: bool) -> FnPart<'a> {
+    // for each count, the competition rank, dense rank and number of ties
+    let mut ranks = HashMap::<usize, (usize, usize, usize)>::new();
+    let mut sorted = counts;
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    for (i, n) in sorted.into_iter().enumerate() {
+        let dense = ranks.len() + 1;
+        ranks.entry(n).or_insert((i + 1, dense, 0)).2 += 1;
+    }
+
+    Box::new(move |r| {
+        let (competition, dense, ties) = ranks.get(&r.count).copied().unwrap_or((r.index, r.index, 1));
+        let s = match (r.kind, rank) {
+            (RowKind::Value, Rank::Competition) => competition.to_string(),
+            (RowKind::Value, Rank::Dense) => dense.to_string(),
+            (RowKind::Value, Rank::Ordinal) => r.index.to_string(),
+            // the average of consecutive ranks is either whole or a half
+            (RowKind::Value, Rank::Fractional) if ties % 2 == 1 => (competition + ties / 2).to_string(),
+            (RowKind::Value, Rank::Fractional) => format!("{}.5", competition + ties / 2 - 1),
+            _ => String::new(),
+        };
+        if lpad { format!("{:>digits$}", s) } else { s }
+    })
+}
+
+fn mk_cnt<'a>(digits: usize, lpad: bool) -> FnPart<'a> {
     let f = mk_fmt_int(digits, lpad);
-    Box::new(move |_i, c, _r, _t| f(c))
+    Box::new(move |r| f(r.count))
 }
 
-fn mk_run(digits: usize, lpad: bool) -> Box<dyn Fn(usize, usize, usize, usize) -> String> {
+fn mk_run<'a>(digits: usize, lpad: bool) -> FnPart<'a> {
     let f = mk_fmt_int(digits, lpad);
-    Box::new(move |_i, _c, r, _t| f(r))
+    Box::new(move |r| f(r.sum))
 }
 
-fn mk_pct(digits: usize, lpad: bool) -> Box<dyn Fn(usize, usize, usize, usize) -> String> {
+fn mk_pct<'a>(digits: usize, lpad: bool) -> FnPart<'a> {
     let f = mk_fmt_pct(digits, lpad);
-    Box::new(move |_i, c, _r, t| f(c, t))
+    Box::new(move |r| f(r.count, r.total))
 }
 
-fn mk_cdf(digits: usize, lpad: bool) -> Box<dyn Fn(usize, usize, usize, usize) -> String> {
+fn mk_cdf<'a>(digits: usize, lpad: bool) -> FnPart<'a> {
     let f = mk_fmt_pct(digits, lpad);
-    Box::new(move |_i, _c, r, t| f(r, t))
+    Box::new(move |r| f(r.sum, r.total))
 }
 
+// 95% margin of error of the percent, for a sample of `n` values
+fn mk_moe<'a>(digits: usize, lpad: bool, n: f64) -> FnPart<'a> {
+    Box::new(move |r| {
+        let p = r.count as f64 / r.total as f64;
+        let moe = 100.0 * 1.96 * (p * (1.0 - p) / n).sqrt();
+        if lpad {
+            format!("{:>w$}", format!("±{:.digits$}", moe), w = digits + 6)
+        } else {
+            format!("{:.digits$}", moe)
+        }
+    })
+}
+
+// one bound of a Wilson score interval for the percent, as a fixed point
+// fraction so that it's formatted the same way as other percentages
+fn mk_ci<'a>(digits: usize, lpad: bool, z: f64, rate: f64, upper: bool) -> FnPart<'a> {
+    const SCALE: usize = 1_000_000_000_000;
+    let f = mk_fmt_pct(digits, lpad);
+    Box::new(move |r| {
+        let (low, high) = wilson(r.count as f64 * rate, r.total as f64 * rate, z);
+        let bound = if upper { high } else { low };
+        f((bound * SCALE as f64).round() as usize, SCALE)
+    })
+}
+
+// count of the row's value within a single input, rows are formatted in
+// order so the (other) row gets whatever wasn't shown before it
+fn mk_input<'a>(counter: Counter<OrderedString>, digits: usize, lpad: bool) -> FnPart<'a> {
+    let f = mk_fmt_int(digits, lpad);
+    let input_total = counter.total::<usize>();
+    let shown = Cell::new(0);
+    Box::new(move |r| f(match r.kind {
+        RowKind::Value => {
+            let n = counter.get(r.value).copied().unwrap_or(0);
+            shown.set(shown.get() + n);
+            n
+        },
+        RowKind::Other => input_total - shown.get(),
+        RowKind::Total => input_total,
+    }))
+}
+
+fn scale_n(n: usize, rate: f64) -> usize {
+    (n as f64 / rate).round() as usize
+}
+
+fn scale(counter: Counter<OrderedString>, rate: f64) -> Counter<OrderedString> {
+    counter.into_iter().map(|(value, n)| (value, scale_n(n, rate))).collect()
+}
+
+
 #[inline(always)]
 fn pf_div(n: usize, p_mod: usize, div: usize) -> (usize, usize) {
     // use 128 bit values to avoid overflows
@@ -238,6 +502,10 @@ impl FatalError {
     }
 }
 
+// empty time buckets are only filled in for spans up to this many buckets
+#[cfg(feature = "time")]
+const MAX_FILLED_BUCKETS: i64 = 100_000;
+
 const DEFAULT_ERROR_CODE: NonZeroI32 = NonZeroI32::new(255).unwrap();
 
 impl<E: Into<Box<dyn std::error::Error>>> From<E> for FatalError {
@@ -267,7 +535,6 @@ impl fmt::Debug for FatalError {
 }
 
 type CounterItem = (OrderedString, usize);
-type FnCmp = fn(&CounterItem, &CounterItem) -> std::cmp::Ordering;
 
 // wrapper around the args class
 #[derive(Debug)]
@@ -275,6 +542,11 @@ pub(crate) struct Freq {
     pub args: FreqArgs,
     pub command: Command,
     pub long_version: bool,
+    // per input counts, only collected with `--by-file`
+    pub by_input: Vec<(String, Counter<OrderedString>)>,
+    // whether values are prefixed with a time bucket
+    #[cfg(feature = "time")]
+    pub series: bool,
 }
 
 impl Freq {
@@ -287,6 +559,9 @@ impl Freq {
             command,
             // HACK clap doesn't seem to have a way to differentiate long vs short flags...
             long_version: std::env::args().any(|arg| arg == "--version"),
+            by_input: Vec::new(),
+            #[cfg(feature = "time")]
+            series: false,
         })
     }
 
@@ -349,105 +624,255 @@ impl Freq {
             LineWriter::new(Box::new(io::stdout().lock()))
         };
 
-        #[cfg(feature = "_regex")]
-        let mut counter = if let Some(ref re) = self.args.regex {
-            self.counter_regex(&Regex::new(re)?)?
-        } else {
-            self.counter()?
+        // the baseline is counted first, since counting takes the input files
+        let baseline = match self.args.diff.clone() {
+            Some(path) => Some(self.baseline(path)?),
+            None => None,
         };
 
-        #[cfg(not(feature = "_regex"))]
-        let mut counter = self.counter()?;
+        let mut counter = self.count()?;
+
+        #[cfg(feature = "time")]
+        let series = self.series;
+        #[cfg(not(feature = "time"))]
+        let series = false;
+
+        for path in self.args.load_state.iter() {
+            let loaded = state::load(path, series)?;
+            if self.args.by_file {
+                counter += loaded.clone();
+                self.by_input.push((path.clone(), loaded));
+            } else {
+                counter += loaded;
+            }
+        }
+
+        if let Some(ref path) = self.args.save_state {
+            state::save(path, self.args.force, &counter, series)?;
+        }
+
+        if let Some(baseline) = baseline {
+            return self.diff(baseline, counter, &mut out);
+        }
 
         // return success if there's no data
         if counter.is_empty() {
             return Ok(0);
         }
 
-        let distinct = counter.len();
-        let total = counter.total::<usize>();
+        #[cfg(feature = "time")]
+        if self.series {
+            return self.time_series(counter, &mut out);
+        }
+
+        if self.args.crosstab {
+            return self.crosstab(counter, &mut out);
+        }
+
+        // numeric values are counted by bin instead
+        if let Some(binner) = Binner::new(&self.args, &counter) {
+            counter = binner.apply(counter);
+            self.by_input = take(&mut self.by_input).into_iter()
+                .map(|(label, counter)| (label, binner.apply(counter)))
+                .collect();
+
+            if counter.is_empty() {
+                return Ok(0);
+            }
+        }
+
+        let mut distinct = counter.len();
+        let mut total = counter.total::<usize>();
+
+        // sampled values have exact counts, but only the total can be estimated
+        if let (Some(rate), SampleBy::Key) = (self.args.sample, self.args.sample_by) {
+            total = scale_n(total, rate);
+        }
 
         // drain/collect instead of Counter::most_common_ordered saves memory
         let mut items: Vec<CounterItem> = counter.drain().collect();
 
-        // sort according to options
-        match (self.args.no_freq_sort, self.args.unstable) {
-            (false, true) => { // sort by frequency only
-                items.sort_unstable_by(self.cmp_freq());
-            },
-            (true, false) => { // sort by string only
-                items.sort_unstable_by(self.cmp_str());
-            },
-            (false, false) => { // sort by frequency, then string
-                items.sort_unstable_by(|a, b| self.cmp_freq()(a, b).then_with(|| self.cmp_str()(a, b)));
-            },
-            (true, true) => (), // don't sort at all
+        if self.args.bins.is_some() {
+            // bins are kept in numeric order
+            items.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            if self.args.reverse {
+                items.reverse();
+            }
+        } else {
+            self.sort_items(&mut items);
+        }
+
+        let summary = if self.args.stats || self.args.stats_only {
+            let counts = items.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+            Some(Summary::new(&counts, self.args.limit.unwrap_or(10)))
+        } else {
+            None
+        };
+
+        if self.args.stats_only {
+            self.write_summary(&summary.unwrap(), &mut out)?;
+            return Ok(0);
+        }
+
+        if self.args.filter_first {
+            let min = self.args.min.unwrap_or(0);
+            let max = self.args.max.map_or(usize::MAX, |max| max.get());
+            items.retain(|(_, n)| *n >= min && *n <= max);
+
+            // filtered values are left out entirely rather than hidden
+            if self.args.pct_of == PctOf::Filtered {
+                distinct = items.len();
+                total = items.iter().map(|(_, n)| n).sum();
+
+                // nothing is left to show, or to compute percentages against
+                if items.is_empty() {
+                    if let Some(summary) = summary {
+                        self.write_summary(&summary, &mut out)?;
+                    }
+                    return Ok(0);
+                }
+            }
         }
 
         let mut sum = 0;
-        let most = items[0].1;
+        let most = items.first().map_or(0, |(_, n)| *n);
 
         let digits = usize::try_from(self.args.digits).unwrap();
         let lpad = !(self.args.tsv || self.args.csv);
 
-        let mut parts = Vec::<Box<dyn Fn(usize, usize, usize, usize) -> String>>::new();
+        let mut parts = Vec::<FnPart>::new();
+        let mut names = Vec::<String>::new();
+        let mut widths = Vec::<usize>::new();
+
+        // column headers are only needed to identify the inputs, and columns
+        // are widened to fit them in the standard layout
+        let header = self.args.by_file && !self.args.unique;
+        let mut push = |part: FnPart<'static>, name: String, width: usize| {
+            let w = 1 + name.chars().count();
+            if header && lpad && w > width {
+                parts.push(Box::new(move |r| format!("{:>w$}", part(r))));
+                widths.push(w);
+            } else {
+                parts.push(part);
+                widths.push(width);
+            }
+            names.push(name);
+        };
+
+        let pct_width = if digits > 0 { 5 + digits } else { 4 };
 
         // number lines
-        if self.args.number {
-            parts.push(mk_idx(max(6, 1 + n_width(distinct)), lpad));
+        if let Some(rank) = self.args.rank {
+            // room for a trailing `.5`
+            let width = max(6, if rank == Rank::Fractional { 3 } else { 1 } + n_width(distinct));
+            let counts = items.iter().map(|(_, n)| *n).collect();
+            push(mk_rank(rank, counts, width, lpad), String::from("n"), width);
+        } else if self.args.number {
+            let width = max(6, 1 + n_width(distinct));
+            push(mk_idx(width, lpad), String::from("n"), width);
         }
 
-        parts.push(mk_cnt(max(7, 1 + n_width(most)), lpad));
+        let width = max(7, 1 + n_width(most));
+        push(mk_cnt(width, lpad), String::from("count"), width);
+
+        // per input counts
+        for (label, counter) in take(&mut self.by_input) {
+            push(mk_input(counter, width, lpad), label, width);
+        }
 
         // running sum total
         if self.args.sum {
             let total = items.iter().fold(0, |accum, item| accum + item.1);
-            parts.push(mk_run(max(7, 1 + n_width(total)), lpad));
+            let width = max(7, 1 + n_width(total));
+            push(mk_run(width, lpad), String::from("sum"), width);
         }
 
         // percent of total
         if !self.args.no_pct {
-            parts.push(mk_pct(digits, lpad));
+            push(mk_pct(digits, lpad), String::from("pct"), pct_width);
+
+            if let (Some(rate), SampleBy::Line) = (self.args.sample, self.args.sample_by) {
+                push(mk_moe(digits, lpad, total as f64 * rate), String::from("pct_moe"), digits + 6);
+            }
+
+            if self.args.ci {
+                // scaled up estimates from sampling aren't the actual sample size
+                let rate = match self.args.sample_by {
+                    SampleBy::Line => self.args.sample.unwrap_or(1.0),
+                    SampleBy::Key => 1.0,
+                };
+                let z = normal_quantile(0.5 + self.args.ci_level / 200.0);
+                push(mk_ci(digits, lpad, z, rate, false), String::from("pct_low"), pct_width);
+                push(mk_ci(digits, lpad, z, rate, true), String::from("pct_high"), pct_width);
+            }
         }
 
         // cumulative distribution function
         if !self.args.no_cdf {
-            parts.push(mk_cdf(digits, lpad));
+            push(mk_cdf(digits, lpad), String::from("cdf"), pct_width);
         }
 
+        names.push(String::from("value"));
+
         // yay closures?
         let format_parts =
-            move |i, c, r, t| parts.iter().map(|f| f(i, c, r, t)).collect::<Vec<String>>();
+            move |r: &Row| parts.iter().map(|f| f(r)).collect::<Vec<String>>();
 
         // formatter (closures are, like, four layers deep at this point...)
-        let f: Box<dyn Fn(usize, usize, usize, usize, String) -> String> = if self.args.unique {
-            Box::new(move |_i, _c, _r, _t, v| v.to_string())
+        let f: Box<dyn Fn(&Row) -> String> = if self.args.unique {
+            Box::new(move |r| r.value.to_string())
         } else if self.args.csv {
             // comma seperated
-            Box::new(move |i, c, r, t, v| {
-                let esc = v
-                    .replace("\\", "\\\\")
-                    .replace(",", "\\,")
-                    .replace("\"", "\\\"");
-
-                format!("{},\"{}\"", format_parts(i, c, r, t).join(","), esc)
-            })
+            Box::new(move |r| format!("{},\"{}\"", format_parts(r).join(","), csv_escape(r.value)))
         } else if self.args.tsv {
             // tab delimited
-            Box::new(move |i, c, r, t, v| format!("{}\t{}", format_parts(i, c, r, t).join("\t"), v))
+            Box::new(move |r| format!("{}\t{}", format_parts(r).join("\t"), r.value.as_ref()))
         } else {
             // standard
-            Box::new(move |i, c, r, t, v| format!("{}  {}", format_parts(i, c, r, t).join(""), v))
+            Box::new(move |r| format!("{}  {}", format_parts(r).join(""), r.value.as_ref()))
         };
 
+        if header {
+            let header = match self.layout() {
+                Layout::Csv => names.iter()
+                    .map(|name| format!("\"{}\"", csv_escape(name)))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                Layout::Tsv => names.join("\t"),
+                Layout::Standard => format!(
+                    "{}  value",
+                    names.iter().zip(widths.iter()).map(|(name, w)| format!("{:>w$}", name)).collect::<String>(),
+                ),
+            };
+            out.write_all(header.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
         let limit = self.args.limit.unwrap_or(usize::MAX);
+        // with ties, the limit extends to values with the same count as the last
+        let last = match items.get(limit.wrapping_sub(1)) {
+            Some((_, n)) if self.args.with_ties => Some(*n),
+            _ => None,
+        };
+        let (mut shown_count, mut shown_distinct) = (0, 0);
+        let mut selected = Vec::new();
+
+        // compare percentages as they're printed, in units of the last digit
+        let p_mod = 10_usize.pow(digits.try_into().unwrap());
+        let shown = |n: usize| {
+            let (whole, frac) = pf_div(n, p_mod, total);
+            whole as u128 * p_mod as u128 + frac as u128
+        };
+        let reaches = |n: usize, (digits, places): (u64, u32)| {
+            shown(n) * 10_u128.pow(places) >= u128::from(digits) * p_mod as u128
+        };
 
         for (index, count, value) in items
             .into_iter()
             .enumerate()
             .map(|(i, (v, c))| (i + 1, c, v))
         {
-            if index > limit { break; }
+            if index > limit && last != Some(count) { break; }
 
             sum += count;
 
@@ -463,13 +888,278 @@ impl Freq {
                 }
             }
 
-            out.write_all(f(index, count, sum, total, value.into()).as_bytes())?;
+            if let Some(min_pct) = self.args.min_pct {
+                if !reaches(count, min_pct) {
+                    continue;
+                }
+            }
+
+            selected.push((index, (value, count), sum));
+
+            if let Some(until_cdf) = self.args.until_cdf {
+                if reaches(sum, until_cdf) {
+                    break;
+                }
+            }
+        }
+
+        // only the values picked by frequency are reordered
+        if let Some(ref keys) = self.args.order_by {
+            selected.sort_by(|(_, a, _), (_, b, _)| sort::compare(keys, a, b));
+
+            let mut sum = 0;
+            for (_, (_, count), row_sum) in selected.iter_mut() {
+                sum += *count;
+                *row_sum = sum;
+            }
+        }
+
+        for (index, (value, count), sum) in selected {
+            out.write_all(f(&Row { kind: RowKind::Value, index, count, sum, total, value: &value }).as_bytes())?;
             out.write_all(b"\n")?;
+            shown_count += count;
+            shown_distinct += 1;
         }
 
+        let label = |name: &str, n: usize| {
+            OrderedString::new(0, format!("({}: {} value{})", name, n, if n == 1 { "" } else { "s" }))
+        };
+
+        // combined rows use the same total as percentages, which is an estimate
+        // when sampling by value
+        if self.args.other && shown_distinct < distinct {
+            let value = label("other", distinct - shown_distinct);
+            let row = Row { kind: RowKind::Other, index: 0, count: total.saturating_sub(shown_count), sum: total, total, value: &value };
+            out.write_all(f(&row).as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        if self.args.total {
+            let value = label("total", distinct);
+            let row = Row { kind: RowKind::Total, index: 0, count: total, sum: total, total, value: &value };
+            out.write_all(f(&row).as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        if let Some(summary) = summary {
+            out.write_all(b"\n")?;
+            self.write_summary(&summary, &mut out)?;
+        }
+
+        Ok(0)
+    }
+
+    fn write_summary(&self, summary: &Summary, out: &mut impl Write) -> Result<(), FatalError> {
+        let digits = usize::try_from(self.args.digits).unwrap();
+        let pct = mk_fmt_pct(digits, false);
+        let float = |v: f64| format!("{:.digits$}", v);
+        let top_n = self.args.limit.unwrap_or(10);
+
+        let rows = [
+            (String::from("total"), summary.total.to_string()),
+            (String::from("distinct"), summary.distinct.to_string()),
+            (String::from("singletons"), summary.singletons.to_string()),
+            (String::from("entropy"), float(summary.entropy)),
+            (String::from("normalized entropy"), float(summary.normalized_entropy)),
+            (String::from("gini"), float(summary.gini)),
+            (String::from("zipf exponent"), summary.zipf.map_or_else(|| String::from("n/a"), float)),
+            (format!("top {} coverage", top_n), pct(summary.top, summary.total)),
+            (String::from("median count"), float(summary.median)),
+            (String::from("mean count"), float(summary.mean)),
+        ];
+
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in rows.iter() {
+            let line = if self.args.csv {
+                format!("\"{}\",{}", name, value)
+            } else if self.args.tsv {
+                format!("{}\t{}", name, value)
+            } else {
+                format!("{:<width$}  {}", name, value)
+            };
+            out.write_all(line.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    fn crosstab(&self, counter: Counter<OrderedString>, out: &mut impl Write) -> Result<i32, FatalError> {
+        let mut xt = Crosstab::from_counter(counter).map_err(|value| FatalError::ClapFmt(
+            NonZeroI32::new(1).unwrap(),
+            self.command().error(
+                clap::error::ErrorKind::ValueValidation,
+                format!("Value `{}` can't be split into a row and column for `--crosstab`", value),
+            )
+        ))?;
+
+        self.sort_items(&mut xt.rows);
+        self.sort_items(&mut xt.cols);
+
+        if let Some(min) = self.args.min {
+            xt.rows.retain(|(_, count)| *count >= min);
+        }
+
+        if let Some(max) = self.args.max {
+            xt.rows.retain(|(_, count)| *count <= max.into());
+        }
+
+        xt.rows.truncate(self.args.limit.unwrap_or(usize::MAX));
+        xt.cols.truncate(self.args.columns.unwrap_or(usize::MAX));
+
+        let digits = usize::try_from(self.args.digits).unwrap();
+        let row_pct = if self.args.row_pct { Some(mk_fmt_pct(digits, false)) } else { None };
+
+        xt.write(out, self.layout(), row_pct)?;
+
+        Ok(0)
+    }
+
+    fn diff(&self, baseline: Counter<OrderedString>, current: Counter<OrderedString>, out: &mut impl Write) -> Result<i32, FatalError> {
+        #[cfg(feature = "time")]
+        if self.series {
+            return Err(FatalError::ClapFmt(
+                NonZeroI32::new(1).unwrap(),
+                self.command().error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "Counts over time can't be compared with `--diff`",
+                )
+            ));
+        }
+
+        // sorting by significance needs a test
+        let test = match (self.args.test, self.args.diff_sort) {
+            (None, DiffSort::Score) => Some(Test::G2),
+            (test, _) => test,
+        };
+
+        let mut diff = Diff::new(baseline, current, test);
+        diff.sort(self.args.diff_sort, self.args.reverse);
+
+        let digits = usize::try_from(self.args.digits).unwrap();
+        diff.write(out, self.layout(), digits, self.args.limit.unwrap_or(usize::MAX))?;
+
+        Ok(0)
+    }
+
+    #[cfg(feature = "time")]
+    fn time_series(&self, counter: Counter<OrderedString>, out: &mut impl Write) -> Result<i32, FatalError> {
+        // `--time-field` conflicts with these, but a `t` group in a regex doesn't
+        let unsupported = [
+            (self.args.crosstab, "--crosstab"),
+            (self.args.bins.is_some(), "--bins"),
+            (self.args.by_file, "--by-file"),
+            (self.args.stats, "--stats"),
+            (self.args.stats_only, "--stats-only"),
+            (self.args.other, "--other"),
+            (self.args.total, "--total"),
+            (self.args.number, "--number"),
+            (self.args.rank.is_some(), "--rank"),
+            (self.args.unique, "--unique"),
+            (self.args.sum, "--sum"),
+            (self.args.ci, "--ci"),
+            (self.args.min_pct.is_some(), "--min-pct"),
+            (self.args.until_cdf.is_some(), "--until-cdf"),
+            (self.args.filter_first, "--filter-first"),
+            (self.args.with_ties, "--with-ties"),
+            (self.args.order_by.is_some(), "--order-by"),
+        ];
+        if let Some((_, name)) = unsupported.iter().find(|(used, _)| *used) {
+            return Err(FatalError::ClapFmt(
+                NonZeroI32::new(1).unwrap(),
+                self.command().error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    format!("Counts over time can't be used with `{}`", name),
+                )
+            ));
+        }
+
+        // rows are time buckets, columns are values
+        let mut xt = Crosstab::from_counter(counter)
+            .expect("time series values are always prefixed");
+
+        self.sort_items(&mut xt.cols);
+
+        if let Some(min) = self.args.min {
+            xt.cols.retain(|(_, count)| *count >= min);
+        }
+
+        if let Some(max) = self.args.max {
+            xt.cols.retain(|(_, count)| *count <= max.into());
+        }
+
+        xt.cols.truncate(self.args.limit.unwrap_or(usize::MAX));
+
+        // fill in any empty buckets so the rows are evenly spaced
+        let width = self.args.bucket;
+        let totals = xt.rows.drain(..)
+            .map(|(t, count)| (t.parse::<i64>().unwrap(), count))
+            .collect::<HashMap<_, _>>();
+        let first = *totals.keys().min().unwrap();
+        let last = *totals.keys().max().unwrap();
+        let mut buckets = if (last - first) / width < MAX_FILLED_BUCKETS {
+            (first..=last).step_by(width.try_into().unwrap()).collect::<Vec<_>>()
+        } else {
+            // a few outliers shouldn't produce a row for every bucket between
+            // them, so only buckets with data are shown
+            totals.keys().copied().collect::<Vec<_>>()
+        };
+        buckets.sort_unstable();
+        xt.rows = buckets.into_iter()
+            .enumerate()
+            .map(|(i, t)| (OrderedString::new(i, t.to_string()), totals.get(&t).copied().unwrap_or(0)))
+            .collect();
+        xt.row_label = Some(format_bucket);
+
+        xt.write(out, self.layout(), None)?;
+
         Ok(0)
     }
 
+    #[cfg(feature = "time")]
+    fn time_bucket(&self) -> TimeBucket {
+        TimeBucket::new(self.args.time_format.clone(), self.args.bucket)
+    }
+
+    fn layout(&self) -> Layout {
+        if self.args.csv {
+            Layout::Csv
+        } else if self.args.tsv {
+            Layout::Tsv
+        } else {
+            Layout::Standard
+        }
+    }
+
+    fn sort_items(&self, items: &mut [CounterItem]) {
+        let keys = self.sort_keys();
+        if !keys.is_empty() {
+            items.sort_unstable_by(|a, b| sort::compare(&keys, a, b));
+        }
+    }
+
+    // the sort order from `--sort`, or the equivalent of the older options
+    fn sort_keys(&self) -> Vec<SortKey> {
+        if let Some(ref keys) = self.args.sort {
+            return keys.clone();
+        }
+
+        let freq = SortKey { field: SortField::Count, desc: !self.args.reverse };
+        // sort by lexigraphic or insertion order depending on flag
+        let str = if self.args.lexigraphic {
+            SortKey { field: SortField::Value(Collation::Bytes), desc: false }
+        } else {
+            SortKey { field: SortField::Order, desc: false }
+        };
+
+        match (self.args.no_freq_sort, self.args.unstable) {
+            (false, true) => vec![freq], // sort by frequency only
+            (true, false) => vec![str], // sort by string only
+            (false, false) => vec![freq, str], // sort by frequency, then string
+            (true, true) => Vec::new(), // don't sort at all
+        }
+    }
+
     fn check_version(&self) -> Result<i32, FatalError> {
         if let Some(semver) = self.args.version.as_ref().unwrap().as_ref() {
             let req = VersionReq::parse(semver)?;
@@ -496,7 +1186,7 @@ impl Freq {
         Ok(())
     }
 
-    fn inputs(&mut self) -> Result<Vec<Input<'_>>, FatalError> {
+    fn inputs(&mut self) -> Result<Vec<Input<'static>>, FatalError> {
         // open input files, triggering i/o errors
         let inputs = take(&mut self.args.files).into_iter()
             .map(|f| if f == "-" { None } else { Some(f) })
@@ -517,69 +1207,401 @@ impl Freq {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        if inputs.is_empty() {
+        // saved state can be used without any new input
+        if inputs.is_empty() && self.args.load_state.is_empty() {
             Ok(vec![Input::stdin()?])
         } else {
             Ok(inputs)
         }
     }
 
+    // count the input, using regular expressions if any were given
+    fn count(&mut self) -> Result<Counter<OrderedString>, FatalError> {
+        #[cfg(feature = "_regex")]
+        let mut counter = if !self.args.regex.is_empty() {
+            let patterns = self.patterns()?;
+            #[cfg(feature = "time")]
+            if !self.args.invert_match && patterns.iter().any(|(_, re)| re.capture_names().any(|name| name == Some("t"))) {
+                self.series = true;
+            }
+            self.counter_regex(&patterns)?
+        } else {
+            self.counter()?
+        };
+
+        #[cfg(not(feature = "_regex"))]
+        let mut counter = self.counter()?;
+
+        // sampled lines are scaled up to estimate counts for the entire input
+        if let (Some(rate), SampleBy::Line) = (self.args.sample, self.args.sample_by) {
+            counter = scale(counter, rate);
+            self.by_input = take(&mut self.by_input).into_iter()
+                .map(|(label, counter)| (label, scale(counter, rate)))
+                .collect();
+        }
+
+        Ok(counter)
+    }
+
+    // saved state is used as is, as is previous output if asked for, and
+    // anything else is counted the same way as the input
+    fn baseline(&mut self, path: String) -> Result<Counter<OrderedString>, FatalError> {
+        if state::detect(&path) {
+            return state::load(&path, false);
+        }
+
+        if self.args.diff_format == DiffFormat::Freq {
+            let lines = Input::path(&path)
+                .map_err(|e| FatalError::new(1, format!("Error opening `{}`: {}", path, e)))?
+                .lines()
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let merged = merge::parse(&lines)
+                .map_err(|e| FatalError::new(1, format!("{}: {}", path, e)))?;
+            return Ok(merged.into_iter()
+                .enumerate()
+                .map(|(i, (n, value))| (OrderedString::new(i, value), n))
+                .collect());
+        }
+
+        let files = take(&mut self.args.files);
+        let files_raw = take(&mut self.args.files_raw);
+        self.args.files = vec![path];
+        let counter = self.count();
+        self.args.files = files;
+        self.args.files_raw = files_raw;
+
+        counter
+    }
+
     fn counter(&mut self) -> Result<Counter<OrderedString>, FatalError> {
+        if self.args.merge {
+            return self.counter_merge();
+        }
+
+        if let Some(kind) = self.args.extract {
+            return self.counter_split(|s| extract(kind, s));
+        }
+
+        if let Some(tokenizer) = Tokenizer::new(&self.args)? {
+            return self.counter_split(|s| tokenizer.tokens(s));
+        }
+
+        #[cfg(feature = "time")]
+        if let Some(field) = self.args.time_field {
+            // the rest of the line becomes the value
+            let apply = |i, s: &str| split_field(s, field.get())
+                .map(|(_, rest)| (OrderedString::new(i, rest), 1usize));
+            self.series = true;
+            return self.counter_call(&mk_apply_time(apply, self.time_bucket(), Some(field)));
+        }
+
         self.counter_call(&|i, s| Some((OrderedString::new(i, s.to_string()), 1usize)))
     }
 
-    #[cfg(feature = "_regex")]
-    fn counter_regex(&mut self, re: &Regex) -> Result<Counter<OrderedString>, FatalError> {
-        // create closure to apply regular expression
-        let apply_re = mk_apply_re(re);
-        match apply_re {
-            Ok(ref apply_re) => self.counter_call(apply_re),
-            Err(e) => Err(e.format(&mut self.command())),
+    // previous output is read up front to work out its layout, then counted
+    // as lines of a count and a value
+    fn counter_merge(&mut self) -> Result<Counter<OrderedString>, FatalError> {
+        let inputs = self.inputs()?.into_iter().map(|i| {
+            let label = i.get_label().to_string();
+            let lines = i.lines().collect::<Result<Vec<_>, _>>()?;
+            let merged = merge::parse(&lines).map_err(|e| FatalError::new(1, format!("{}: {}", label, e)))?;
+
+            let text = merged.into_iter().map(|(n, value)| format!("{}\t{}\n", n, value)).collect::<String>();
+            Ok(Input::reader(Cursor::new(text.into_bytes()), label)?)
+        }).collect::<Result<Vec<_>, FatalError>>()?;
+
+        self.counter_inputs(inputs, &|i, s| {
+            let (n, value) = s.split_once('\t')?;
+            Some((OrderedString::new(i, value.to_string()), n.parse().ok()?))
+        })
+    }
+
+    // count several values from each line, such as extracted tokens
+    fn counter_split<G: Fn(&str) -> Vec<String>>(&mut self, split: G) -> Result<Counter<OrderedString>, FatalError> {
+        #[cfg(feature = "time")]
+        if let Some(field) = self.args.time_field {
+            // the timestamp field isn't split along with the rest of the line
+            let bucket = self.time_bucket();
+            self.series = true;
+            return self.counter_call(&|i, s: &str| {
+                let Some((_, rest)) = split_field(s, field.get()) else {
+                    return Vec::new();
+                };
+                split(&rest).into_iter()
+                    .filter_map(|value| time_prefix(&bucket, Some(field), i, s, (OrderedString::new(i, value), 1usize)))
+                    .collect::<Vec<_>>()
+            });
         }
+
+        self.counter_call(&|i, s| split(s).into_iter().map(move |value| (OrderedString::new(i, value), 1usize)))
     }
 
-    #[allow(dead_code)]
-    fn counter_call<F: Fn(usize, &str) -> Option<(OrderedString, usize)>>(&mut self, f: &F) -> Result<Counter<OrderedString>, FatalError> {
-        let skip = if self.args.skip_header { 1 } else { 0 };
-        // run the counter over the lines
-        Ok(self.inputs()?
-            .into_iter()
-            .flat_map(|i| {
-                let label = i.get_label().to_string();
-                i.lines()
-                    .enumerate()
-                    .skip(skip)
-                    .filter_map(move |(index, line)| {
-                        match line {
-                            Err(e) => {
-                                eprintln!(
-                                    "{}:{}:Error({}): {}",
-                                    label, index, e.kind(), e,
-                                );
-                                None
-                            },
-                            Ok(s) => f(index, &s),
-                        }
-                    })
+    #[cfg(feature = "_regex")]
+    fn patterns(&self) -> Result<Vec<(String, Regex)>, FatalError> {
+        if self.args.pattern_label.len() > self.args.regex.len() {
+            return Err(FatalError::ClapFmt(
+                NonZeroI32::new(1).unwrap(),
+                self.command().error(
+                    clap::error::ErrorKind::ValueValidation,
+                    "more `--pattern-label` labels than patterns",
+                )
+            ));
+        }
+
+        self.args.regex.iter()
+            .enumerate()
+            .map(|(i, pattern)| {
+                // labels are optional, patterns are numbered by default
+                let label = self.args.pattern_label.get(i).cloned().unwrap_or_else(|| (i + 1).to_string());
+                Ok((label, Regex::new(pattern)?))
             })
-            .collect::<Counter<_>>())
+            .collect()
     }
 
-    fn cmp_freq(&self) -> FnCmp {
-        // sort ascending or descending depending on flag
-        if self.args.reverse {
-            |(_, a), (_, b)| a.cmp(b)
-        } else {
-            |(_, a), (_, b)| b.cmp(a)
+    #[cfg(feature = "_regex")]
+    fn counter_regex(&mut self, patterns: &[(String, Regex)]) -> Result<Counter<OrderedString>, FatalError> {
+        let template = match self.args.replace {
+            Some(ref template) => Some(Template::parse(template).map_err(|e| FatalError::ClapFmt(
+                NonZeroI32::new(1).unwrap(),
+                self.command().error(clap::error::ErrorKind::ValueValidation, e),
+            ))?),
+            None => None,
+        };
+
+        #[cfg(feature = "time")]
+        let series = {
+            self.series |= self.args.time_field.is_some();
+            self.series
+        };
+        #[cfg(not(feature = "time"))]
+        let series = false;
+
+        if self.args.invert_match {
+            // the entire line is the value when no pattern matches
+            let apply_re = |i: usize, s: &str| if patterns.iter().any(|(_, re)| re_is_match(re, s)) {
+                None
+            } else {
+                Some((OrderedString::new(i, s.to_string()), 1usize))
+            };
+
+            #[cfg(feature = "time")]
+            if let Some(field) = self.args.time_field {
+                let apply_time = mk_apply_time(|i, s: &str| {
+                    apply_re(i, s)?;
+                    split_field(s, field.get()).map(|(_, rest)| (OrderedString::new(i, rest), 1usize))
+                }, self.time_bucket(), Some(field));
+                return self.counter_call(&apply_time);
+            }
+
+            return self.counter_call(&apply_re);
+        }
+
+        if patterns.len() == 1 && !self.args.label_patterns && !self.args.all_matches {
+            // create closure to apply regular expression
+            let apply_re = mk_apply_re(&patterns[0].1, template.as_ref())
+                .map_err(|e| e.format(&mut self.command()))?;
+
+            #[cfg(feature = "time")]
+            if series {
+                return self.counter_call(&mk_apply_time(apply_re, self.time_bucket(), self.args.time_field));
+            }
+
+            return self.counter_call(&apply_re);
         }
+
+        // create closures to apply each regular expression
+        let mut applies = Vec::<FnApplyAll>::new();
+        for (_, re) in patterns.iter() {
+            let apply_re = if self.args.all_matches {
+                mk_apply_re_all(re, template.as_ref())
+            } else {
+                mk_apply_re(re, template.as_ref()).map(|apply_re| -> FnApplyAll {
+                    Box::new(move |i, s| apply_re(i, s).into_iter().collect())
+                })
+            }.map_err(|e| e.format(&mut self.command()))?;
+
+            #[cfg(feature = "time")]
+            let apply_re = if series {
+                let (bucket, field) = (self.time_bucket(), self.args.time_field);
+                Box::new(move |i: usize, s: &str| apply_re(i, s).into_iter()
+                    .filter_map(|item| time_prefix(&bucket, field, i, s, item))
+                    .collect())
+            } else {
+                apply_re
+            };
+
+            applies.push(apply_re);
+        }
+
+        let all = self.args.all_patterns;
+        let label_patterns = self.args.label_patterns;
+
+        // try each pattern in turn, stopping at the first match unless all
+        // patterns are to be counted
+        let apply = |i: usize, s: &str| {
+            let mut items = Vec::new();
+            for ((label, _), apply_re) in patterns.iter().zip(applies.iter()) {
+                let matches = apply_re(i, s);
+                if matches.is_empty() {
+                    continue;
+                }
+
+                items.extend(matches.into_iter().map(|(value, n)| {
+                    let value = if !label_patterns {
+                        value
+                    } else if series {
+                        // the label goes after the time bucket
+                        let (t, rest) = value.split_once('\t').unwrap_or((&value, ""));
+                        OrderedString::new(i, format!("{}\t{}\t{}", t, label, rest))
+                    } else {
+                        OrderedString::new(i, format!("{}\t{}", label, value.as_ref()))
+                    };
+                    (value, n)
+                }));
+
+                if !all {
+                    break;
+                }
+            }
+            items
+        };
+
+        self.counter_call(&apply)
     }
 
-    fn cmp_str(&self) -> FnCmp {
-        // sort by lexigraphic or insertion order depending on flag
-        if self.args.lexigraphic {
-            |(a, _), (b, _)| a.as_ref().cmp(b.as_ref())
-        } else {
-            |(a, _), (b, _)| a.cmp(b)
+    #[allow(dead_code)]
+    fn counter_call<F, I>(&mut self, f: &F) -> Result<Counter<OrderedString>, FatalError>
+    where
+        F: Fn(usize, &str) -> I,
+        I: IntoIterator<Item = (OrderedString, usize)>,
+    {
+        let inputs = self.inputs()?;
+        self.counter_inputs(inputs, f)
+    }
+
+    fn counter_inputs<F, I>(&mut self, inputs: Vec<Input>, f: &F) -> Result<Counter<OrderedString>, FatalError>
+    where
+        F: Fn(usize, &str) -> I,
+        I: IntoIterator<Item = (OrderedString, usize)>,
+    {
+        let skip = if self.args.skip_header { 1 } else { 0 };
+        let by_file = self.args.by_file;
+        let mut by_input = Vec::new();
+        let mut counter = Counter::new();
+
+        let rollups = Rollups::new(&self.args);
+        let normalizer = Normalizer::new(&self.args);
+        #[cfg(feature = "unicode")]
+        let keep_spelling = self.args.keep_spelling;
+        #[cfg(not(feature = "unicode"))]
+        let keep_spelling = false;
+        // counts of each original spelling of case folded values
+        let mut spellings = HashMap::<String, Counter<OrderedString>>::new();
+
+        let filter = KeyFilter::new(&self.args)?;
+        let sampler = Sampler::new(&self.args);
+        #[cfg(feature = "time")]
+        let series = self.series;
+        #[cfg(not(feature = "time"))]
+        let series = false;
+
+        // run the counter over the lines of each input
+        for i in inputs {
+            let label = i.get_label().to_string();
+            let input_counter = i.lines()
+                .enumerate()
+                .skip(skip)
+                // skipped lines are never parsed
+                .filter(|_| sampler.as_ref().is_none_or(|sampler| sampler.keep_line()))
+                .filter_map(|(index, line)| {
+                    match line {
+                        Err(e) => {
+                            eprintln!(
+                                "{}:{}:Error({}): {}",
+                                label, index, e.kind(), e,
+                            );
+                            None
+                        },
+                        Ok(s) => Some(f(index, &s)),
+                    }
+                })
+                .flatten()
+                // a line can have several values, so each one gets its own
+                // place in the order rather than the index of its line
+                .enumerate()
+                .map(|(seq, (value, n))| (OrderedString::new(seq, String::from(value)), n))
+                .map(|(value, n)| match rollups {
+                    Some(ref rollups) => (OrderedString::new(*value.order(), rollups.apply(&value)), n),
+                    None => (value, n),
+                })
+                .filter_map(|(value, n)| {
+                    let Some(ref norm) = normalizer else {
+                        return Some((value, n));
+                    };
+
+                    let shaped = norm.shape(&value)?;
+                    let Some(folded) = norm.fold(&shaped) else {
+                        return Some((OrderedString::new(*value.order(), shaped), n));
+                    };
+
+                    if keep_spelling {
+                        let spelling = spellings.entry(folded.clone()).or_default();
+                        *spelling.entry(OrderedString::new(*value.order(), shaped)).or_insert(0) += n;
+                    }
+
+                    Some((OrderedString::new(*value.order(), folded), n))
+                })
+                .filter(|(value, _)| {
+                    if filter.is_none() && sampler.is_none() {
+                        return true;
+                    }
+
+                    // don't include the time bucket
+                    let value = if series {
+                        value.split_once('\t').map_or("", |(_, rest)| rest)
+                    } else {
+                        value.as_ref()
+                    };
+
+                    filter.as_ref().is_none_or(|filter| filter.keep(value))
+                        && sampler.as_ref().is_none_or(|sampler| sampler.keep_key(value))
+                })
+                .collect::<Counter<_>>();
+
+            if by_file {
+                counter += input_counter.clone();
+                by_input.push((label, input_counter));
+            } else {
+                counter += input_counter;
+            }
+        }
+
+        if keep_spelling {
+            // show case folded values using their most common spelling
+            let spellings = spellings.into_iter()
+                .filter_map(|(folded, spelling)| {
+                    // ties go to whichever spelling was seen first
+                    let (best, _) = spelling.into_iter()
+                        .min_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.order().cmp(b.order())))?;
+                    Some((folded, String::from(best)))
+                })
+                .collect::<HashMap<_, _>>();
+            let respell = |counter: Counter<OrderedString>| counter.into_iter()
+                .map(|(value, n)| match spellings.get(value.as_ref()) {
+                    Some(best) => (OrderedString::new(*value.order(), best.clone()), n),
+                    None => (value, n),
+                })
+                .collect::<Counter<_>>();
+
+            counter = respell(counter);
+            by_input = by_input.into_iter()
+                .map(|(label, counter)| (label, respell(counter)))
+                .collect();
         }
+
+        self.by_input = by_input;
+
+        Ok(counter)
     }
 }
@@ -0,0 +1,125 @@
+use crate::ordered::OrderedString;
+use crate::output::{csv_escape, Layout};
+
+use std::cmp::max;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use counter::Counter;
+
+pub type Item = (OrderedString, usize);
+
+pub struct Crosstab {
+    pub rows: Vec<Item>,
+    pub cols: Vec<Item>,
+    pub total: usize,
+    pub row_label: Option<fn(&str) -> String>,
+    cells: HashMap<(String, String), usize>,
+}
+
+// sum counts by key, keeping the earliest position each key was seen at
+fn tally(totals: &mut HashMap<String, (usize, usize)>, order: usize, key: &str, n: usize) {
+    let entry = totals.entry(key.to_string()).or_insert((order, 0));
+    entry.0 = entry.0.min(order);
+    entry.1 += n;
+}
+
+fn items(totals: HashMap<String, (usize, usize)>) -> Vec<Item> {
+    totals.into_iter()
+        .map(|(key, (order, n))| (OrderedString::new(order, key), n))
+        .collect()
+}
+
+impl Crosstab {
+    // split each value into a row key and a column key at the first tab,
+    // returns the offending value if one can't be split
+    pub fn from_counter(counter: Counter<OrderedString>) -> Result<Self, String> {
+        let mut rows = HashMap::new();
+        let mut cols = HashMap::new();
+        let mut cells = HashMap::new();
+        let mut total = 0;
+
+        for (key, n) in counter.into_iter() {
+            let Some((row, col)) = key.split_once('\t') else {
+                return Err(key.to_string());
+            };
+
+            tally(&mut rows, *key.order(), row, n);
+            tally(&mut cols, *key.order(), col, n);
+            *cells.entry((row.to_string(), col.to_string())).or_insert(0) += n;
+            total += n;
+        }
+
+        Ok(Crosstab { rows: items(rows), cols: items(cols), total, row_label: None, cells })
+    }
+
+    pub fn get(&self, row: &str, col: &str) -> usize {
+        // avoiding the allocation would need a borrowed tuple key type
+        self.cells.get(&(row.to_string(), col.to_string())).copied().unwrap_or(0)
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        out: &mut W,
+        layout: Layout,
+        row_pct: Option<Box<dyn Fn(usize, usize) -> String>>,
+    ) -> io::Result<()> {
+        let cell = |n: usize, row_total: usize| match row_pct {
+            Some(ref f) => f(n, row_total),
+            None => n.to_string(),
+        };
+
+        // build the whole table as text first so column widths are known
+        let mut table = Vec::<Vec<String>>::new();
+
+        let mut header = vec![String::new()];
+        header.extend(self.cols.iter().map(|(col, _)| col.to_string()));
+        header.push(String::from("(total)"));
+        table.push(header);
+
+        for (row, row_total) in self.rows.iter() {
+            let mut line = vec![self.row_label.map_or_else(|| row.to_string(), |f| f(row))];
+            line.extend(self.cols.iter().map(|(col, _)| cell(self.get(row, col), *row_total)));
+            line.push(cell(*row_total, *row_total));
+            table.push(line);
+        }
+
+        let mut footer = vec![String::from("(total)")];
+        footer.extend(self.cols.iter().map(|(_, n)| cell(*n, self.total)));
+        footer.push(cell(self.total, self.total));
+        table.push(footer);
+
+        let mut widths = vec![0; table[0].len()];
+        for line in table.iter() {
+            for (w, s) in widths.iter_mut().zip(line.iter()) {
+                *w = max(*w, s.chars().count());
+            }
+        }
+
+        for (i, line) in table.into_iter().enumerate() {
+            let text = match layout {
+                Layout::Standard => line.iter().zip(widths.iter()).enumerate().map(|(j, (s, w))| {
+                    if j == 0 {
+                        format!("{:<w$}", s)
+                    } else {
+                        format!("  {:>w$}", s, w = max(7, *w))
+                    }
+                }).collect::<String>(),
+                Layout::Tsv => line.join("\t"),
+                Layout::Csv => line.iter().enumerate().map(|(j, s)| {
+                    // labels are quoted, numbers aren't
+                    if i == 0 || j == 0 {
+                        format!("\"{}\"", csv_escape(s))
+                    } else {
+                        s.to_string()
+                    }
+                }).collect::<Vec<_>>().join(","),
+            };
+
+            out.write_all(text.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
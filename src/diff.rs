@@ -0,0 +1,195 @@
+use crate::command::{DiffSort, Test};
+use crate::ordered::OrderedString;
+use crate::output::{csv_escape, Layout};
+use crate::stats::{chi2_p, homogeneity};
+
+use std::cmp::{max, Ordering};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use counter::Counter;
+
+struct Change {
+    value: OrderedString,
+    baseline: usize,
+    current: usize,
+    // change in percent of the total
+    share: f64,
+    // significance of the change in share, and its p-value
+    score: f64,
+    p: f64,
+}
+
+impl Change {
+    fn delta(&self) -> i128 {
+        self.current as i128 - self.baseline as i128
+    }
+
+    // values that are new have an infinite ratio
+    fn ratio(&self) -> f64 {
+        self.current as f64 / self.baseline as f64
+    }
+}
+
+pub struct Diff {
+    changes: Vec<Change>,
+    totals: (usize, usize),
+    // whether the distribution as a whole changed
+    overall: Option<(f64, f64)>,
+}
+
+impl Diff {
+    pub fn new(baseline: Counter<OrderedString>, current: Counter<OrderedString>, test: Option<Test>) -> Self {
+        let baseline_total = baseline.total::<usize>();
+        let current_total = current.total::<usize>();
+
+        let mut changes = current.into_iter()
+            .map(|(value, current)| (value.to_string(), Change { value, baseline: 0, current, share: 0.0, score: 0.0, p: 1.0 }))
+            .collect::<HashMap<_, _>>();
+
+        for (value, n) in baseline.into_iter() {
+            changes.entry(value.to_string())
+                .or_insert(Change { value, baseline: 0, current: 0, share: 0.0, score: 0.0, p: 1.0 })
+                .baseline = n;
+        }
+
+        let share = |n: usize, total: usize| if total == 0 { 0.0 } else { 100.0 * n as f64 / total as f64 };
+        let changes = changes.into_values()
+            .map(|change| {
+                // each value against everything else
+                let (score, p) = match test {
+                    Some(test) => {
+                        let current = [change.current, current_total - change.current];
+                        let baseline = [change.baseline, baseline_total - change.baseline];
+                        let (score, df) = homogeneity(test, &[&current, &baseline]);
+                        (score, chi2_p(score, df))
+                    },
+                    None => (0.0, 1.0),
+                };
+
+                Change {
+                    share: share(change.current, current_total) - share(change.baseline, baseline_total),
+                    score,
+                    p,
+                    ..change
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let overall = test.map(|test| {
+            let current = changes.iter().map(|change| change.current).collect::<Vec<_>>();
+            let baseline = changes.iter().map(|change| change.baseline).collect::<Vec<_>>();
+            let (score, df) = homogeneity(test, &[&current, &baseline]);
+            (score, chi2_p(score, df))
+        });
+
+        Diff { changes, totals: (baseline_total, current_total), overall }
+    }
+
+    // biggest changes first, then by current count and value
+    pub fn sort(&mut self, by: DiffSort, reverse: bool) {
+        self.changes.sort_by(|x, y| {
+            let primary = match by {
+                DiffSort::Change => y.delta().abs().cmp(&x.delta().abs()),
+                DiffSort::Delta => y.delta().cmp(&x.delta()),
+                DiffSort::Ratio => y.ratio().partial_cmp(&x.ratio()).unwrap_or(Ordering::Equal),
+                DiffSort::Share => y.share.partial_cmp(&x.share).unwrap_or(Ordering::Equal),
+                DiffSort::Score => x.p.partial_cmp(&y.p).unwrap_or(Ordering::Equal)
+                    .then_with(|| y.score.partial_cmp(&x.score).unwrap_or(Ordering::Equal)),
+            };
+            let primary = if reverse { primary.reverse() } else { primary };
+
+            primary
+                .then_with(|| y.current.cmp(&x.current))
+                .then_with(|| x.value.as_ref().cmp(y.value.as_ref()))
+        });
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W, layout: Layout, digits: usize, limit: usize) -> io::Result<()> {
+        let ratio = |baseline: usize, current: usize| match baseline {
+            0 => String::from("inf"),
+            _ => format!("{:.digits$}", current as f64 / baseline as f64),
+        };
+
+        // tiny p-values would just show as zero
+        let fmt_p = |p: f64| if p > 0.0 && p < 0.001 { format!("{:.2e}", p) } else { format!("{:.4}", p) };
+
+        let mut table = self.changes.iter().take(limit).map(|change| {
+            let flag = match (change.baseline, change.current) {
+                (0, _) => "new",
+                (_, 0) => "gone",
+                _ => "",
+            };
+
+            let mut cols = vec![
+                change.baseline.to_string(),
+                change.current.to_string(),
+                format!("{:+}", change.delta()),
+                ratio(change.baseline, change.current),
+                format!("{:+.digits$}", change.share),
+            ];
+
+            if self.overall.is_some() {
+                cols.push(format!("{:.digits$}", change.score));
+                cols.push(fmt_p(change.p));
+            }
+
+            (cols, flag, change.value.as_ref())
+        }).collect::<Vec<_>>();
+
+        if let Some((score, p)) = self.overall {
+            let (baseline, current) = self.totals;
+            let cols = vec![
+                baseline.to_string(),
+                current.to_string(),
+                format!("{:+}", current as i128 - baseline as i128),
+                ratio(baseline, current),
+                format!("{:+.digits$}", 0.0),
+                format!("{:.digits$}", score),
+                fmt_p(p),
+            ];
+            table.push((cols, "", "(total)"));
+        }
+
+        let mut widths = vec![7; table.first().map_or(0, |(cols, _, _)| cols.len())];
+        for (cols, _, _) in table.iter() {
+            for (w, s) in widths.iter_mut().zip(cols.iter()) {
+                *w = max(*w, s.len() + 1);
+            }
+        }
+
+        // name the columns for other tools, like `--by-file` does
+        if layout != Layout::Standard {
+            let mut names = vec!["baseline", "current", "delta", "ratio", "share"];
+            if self.overall.is_some() {
+                names.extend(["score", "p"]);
+            }
+            names.extend(["flag", "value"]);
+
+            let header = match layout {
+                Layout::Csv => names.iter().map(|name| format!("\"{}\"", name)).collect::<Vec<_>>().join(","),
+                _ => names.join("\t"),
+            };
+            out.write_all(header.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        for (cols, flag, value) in table.into_iter() {
+            let text = match layout {
+                Layout::Standard => {
+                    let numbers = cols.iter().zip(widths.iter())
+                        .map(|(s, w)| format!("{:>w$}", s))
+                        .collect::<String>();
+                    format!("{}  {:<4}  {}", numbers, flag, value)
+                },
+                Layout::Tsv => format!("{}\t{}\t{}", cols.join("\t"), flag, value),
+                Layout::Csv => format!("{},\"{}\",\"{}\"", cols.join(","), flag, csv_escape(value)),
+            };
+
+            out.write_all(text.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use crate::command::Extract;
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// a token found in a line, with its position so overlaps can be resolved
+struct Token {
+    start: usize,
+    end: usize,
+    value: String,
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+// maximal runs of bytes matching a predicate, as (start, end) pairs
+fn runs(s: &[u8], pred: impl Fn(u8) -> bool) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+
+    for (i, b) in s.iter().copied().enumerate() {
+        match (start, pred(b)) {
+            (None, true) => start = Some(i),
+            (Some(j), false) => {
+                runs.push((j, i));
+                start = None;
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(j) = start {
+        runs.push((j, s.len()));
+    }
+
+    runs
+}
+
+// whether the bytes either side of a token aren't part of a word
+fn bounded(s: &[u8], start: usize, end: usize) -> bool {
+    (start == 0 || !is_word(s[start - 1])) && (end == s.len() || !is_word(s[end]))
+}
+
+// shrink a range to exclude leading and trailing bytes matching a predicate
+fn trim(s: &[u8], mut start: usize, mut end: usize, pred: impl Fn(u8) -> bool) -> (usize, usize) {
+    while start < end && pred(s[start]) {
+        start += 1;
+    }
+
+    while end > start && pred(s[end - 1]) {
+        end -= 1;
+    }
+
+    (start, end)
+}
+
+fn ipv4(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    runs(s, |b| b.is_ascii_digit() || b == b'.')
+        .into_iter()
+        .map(|(start, end)| trim(s, start, end, |b| b == b'.'))
+        .filter(|&(start, end)| bounded(s, start, end))
+        .filter_map(|(start, end)| {
+            let addr = line[start..end].parse::<Ipv4Addr>().ok()?;
+            Some(Token { start, end, value: addr.to_string() })
+        })
+        .collect()
+}
+
+fn ipv6(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    runs(s, |b| b.is_ascii_hexdigit() || b == b':' || b == b'.')
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let (mut start, end) = trim(s, start, end, |b| b == b'.');
+
+            // a single leading colon is a separator, like in `client:2001:db8::1`
+            if s[start..end].starts_with(b":") && !s[start..end].starts_with(b"::") {
+                start += 1;
+            }
+
+            let candidate = &line[start..end];
+            if candidate.matches(':').count() < 2 || !bounded(s, start, end) {
+                return None;
+            }
+
+            if let Ok(addr) = candidate.parse::<Ipv6Addr>() {
+                return Some(Token { start, end, value: addr.to_string() });
+            }
+
+            // try dropping a trailing port or separator
+            let (rest, _) = candidate.rsplit_once(':')?;
+            let addr = rest.parse::<Ipv6Addr>().ok()?;
+            Some(Token { start, end: start + rest.len(), value: addr.to_string() })
+        })
+        .collect()
+}
+
+fn ip(line: &str) -> Vec<Token> {
+    let v6 = ipv6(line);
+
+    // addresses like `::ffff:192.0.2.1` contain an IPv4 address
+    let mut tokens = ipv4(line).into_iter()
+        .filter(|v4| !v6.iter().any(|v6| v4.start >= v6.start && v4.end <= v6.end))
+        .collect::<Vec<_>>();
+
+    tokens.extend(v6);
+
+    tokens.sort_by_key(|token| token.start);
+    tokens
+}
+
+fn valid_domain(domain: &str) -> bool {
+    let labels = domain.split('.').collect::<Vec<_>>();
+    let tld = labels[labels.len() - 1];
+
+    labels.len() >= 2
+        && tld.len() >= 2
+        && tld.bytes().all(|b| b.is_ascii_alphabetic())
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+}
+
+fn email(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    let local = |b: u8| is_word(b) || b".%+-".contains(&b);
+    let host = |b: u8| b.is_ascii_alphanumeric() || b == b'.' || b == b'-';
+
+    line.match_indices('@')
+        .filter_map(|(at, _)| {
+            let mut start = at;
+            while start > 0 && local(s[start - 1]) {
+                start -= 1;
+            }
+
+            let mut end = at + 1;
+            while end < s.len() && host(s[end]) {
+                end += 1;
+            }
+
+            let (start, _) = trim(s, start, at, |b| b == b'.');
+            let (_, end) = trim(s, at + 1, end, |b| b == b'.' || b == b'-');
+            let domain = &line[at + 1..end];
+
+            if start == at || !valid_domain(domain) {
+                return None;
+            }
+
+            let value = format!("{}@{}", &line[start..at], domain.to_ascii_lowercase());
+            Some(Token { start, end, value })
+        })
+        .collect()
+}
+
+fn url(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    let scheme = |b: u8| b.is_ascii_alphanumeric() || b"+.-".contains(&b);
+    let stop = |b: u8| b.is_ascii_whitespace() || b"\"'<>`{}|\\^".contains(&b);
+
+    let mut tokens = Vec::<Token>::new();
+    for (sep, _) in line.match_indices("://") {
+        // overlapping with a previous url, e.g. `?next=http://...`
+        if tokens.last().is_some_and(|token| token.end > sep) {
+            continue;
+        }
+
+        let mut start = sep;
+        while start > 0 && scheme(s[start - 1]) {
+            start -= 1;
+        }
+
+        // schemes have to start with a letter
+        while start < sep && !s[start].is_ascii_alphabetic() {
+            start += 1;
+        }
+
+        let mut end = sep + 3;
+        while end < s.len() && !stop(s[end]) {
+            end += 1;
+        }
+
+        // drop trailing punctuation, but keep balanced parentheses
+        loop {
+            let (_, trimmed) = trim(s, start, end, |b| b".,;:!?".contains(&b));
+            end = trimmed;
+
+            let text = &line[start..end];
+            if text.ends_with(')') && text.matches(')').count() > text.matches('(').count() {
+                end -= 1;
+            } else {
+                break;
+            }
+        }
+
+        if start < sep && end > sep + 3 {
+            tokens.push(Token { start, end, value: line[start..end].to_string() });
+        }
+    }
+
+    tokens
+}
+
+fn domain(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    runs(s, |b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+        .into_iter()
+        .map(|(start, end)| trim(s, start, end, |b| b == b'.' || b == b'-'))
+        // the local part of an email address can look like a domain
+        .filter(|&(start, end)| start < end && s.get(end) != Some(&b'@'))
+        .filter(|&(start, end)| bounded(s, start, end) && valid_domain(&line[start..end]))
+        .map(|(start, end)| Token { start, end, value: line[start..end].to_ascii_lowercase() })
+        .collect()
+}
+
+fn uuid(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    runs(s, |b| b.is_ascii_hexdigit() || b == b'-')
+        .into_iter()
+        .filter(|&(start, end)| {
+            let groups = line[start..end].split('-').map(str::len).collect::<Vec<_>>();
+            groups == [8, 4, 4, 4, 12] && bounded(s, start, end)
+        })
+        .map(|(start, end)| Token { start, end, value: line[start..end].to_ascii_lowercase() })
+        .collect()
+}
+
+fn hex(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    runs(s, is_word)
+        .into_iter()
+        .filter(|&(start, end)| {
+            // parts of hyphenated ids like UUIDs aren't counted by themselves
+            let hyphenated = (start > 0 && s[start - 1] == b'-') || (end < s.len() && s[end] == b'-');
+            let word = &line[start..end];
+            !hyphenated && match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                Some(digits) => !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()),
+                // bare hex needs to look like a hash or id rather than a word or number
+                None => word.len() >= 8
+                    && word.bytes().all(|b| b.is_ascii_hexdigit())
+                    && word.bytes().any(|b| b.is_ascii_digit())
+                    && word.bytes().any(|b| b.is_ascii_alphabetic()),
+            }
+        })
+        .map(|(start, end)| Token { start, end, value: line[start..end].to_ascii_lowercase() })
+        .collect()
+}
+
+fn number(line: &str) -> Vec<Token> {
+    let s = line.as_bytes();
+    let digits = |mut i: usize| {
+        while i < s.len() && s[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+
+    // numbers joined to other text, like in `1.2.3`, `12:30`, or `2024-01-02`,
+    // are part of a larger token rather than numbers by themselves
+    let joiner = |b: u8| b == b'.' || b == b':' || b == b'-';
+    let joined_before = |i: usize| i > 0 && (is_word(s[i - 1]) || (i > 1 && joiner(s[i - 1]) && is_word(s[i - 2])));
+    let joined_after = |i: usize| i < s.len() && (is_word(s[i]) || (i + 1 < s.len() && joiner(s[i]) && is_word(s[i + 1])));
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        if !s[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let mut end = digits(i);
+        if end + 1 < s.len() && s[end] == b'.' && s[end + 1].is_ascii_digit() {
+            end = digits(end + 1);
+        }
+
+        if end + 1 < s.len() && (s[end] == b'e' || s[end] == b'E') {
+            let exp = if s[end + 1] == b'-' || s[end + 1] == b'+' { end + 2 } else { end + 1 };
+            if exp < s.len() && s[exp].is_ascii_digit() {
+                end = digits(exp);
+            }
+        }
+
+        if joined_before(i) || joined_after(end) {
+            // skip the rest of the larger token
+            i = end;
+            while i < s.len() && (is_word(s[i]) || joiner(s[i])) {
+                i += 1;
+            }
+            continue;
+        }
+
+        let start = if i > 0 && (s[i - 1] == b'-' || s[i - 1] == b'+') { i - 1 } else { i };
+        tokens.push(Token { start, end, value: line[start..end].to_string() });
+        i = end;
+    }
+
+    tokens
+}
+
+// find every token of a type in a line
+pub fn extract(kind: Extract, line: &str) -> Vec<String> {
+    let tokens = match kind {
+        Extract::Ip => ip(line),
+        Extract::Ipv4 => ipv4(line),
+        Extract::Ipv6 => ipv6(line),
+        Extract::Email => email(line),
+        Extract::Url => url(line),
+        Extract::Domain => domain(line),
+        Extract::Uuid => uuid(line),
+        Extract::Hex => hex(line),
+        Extract::Number => number(line),
+    };
+
+    tokens.into_iter().map(|token| token.value).collect()
+}
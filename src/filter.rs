@@ -0,0 +1,92 @@
+use crate::command::{FatalError, FreqArgs};
+use crate::input::Input;
+
+use std::collections::HashSet;
+use std::io::BufRead;
+
+#[cfg(all(feature = "regex-basic", not(feature = "regex-fancy")))]
+use regex::Regex;
+#[cfg(feature = "regex-fancy")]
+use fancy_regex::Regex;
+
+enum Matcher {
+    Exact(HashSet<String>),
+    #[cfg(feature = "_regex")]
+    Pattern(Regex),
+}
+
+impl Matcher {
+    fn load(paths: &[String], patterns: bool) -> Result<Option<Self>, FatalError> {
+        if paths.is_empty() {
+            return Ok(None);
+        }
+
+        let mut lines = HashSet::new();
+        for path in paths.iter() {
+            let input = Input::path(path).map_err(|e| {
+                FatalError::new(1, format!("Error opening `{}`: {}", path, e))
+            })?;
+
+            for line in input.lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    lines.insert(line);
+                }
+            }
+        }
+
+        // an empty alternation would match everything, rather than nothing
+        #[cfg(feature = "_regex")]
+        if patterns && !lines.is_empty() {
+            // a single alternation works for both regex backends
+            let re = lines.iter()
+                .map(|line| format!("(?:{})", line))
+                .collect::<Vec<_>>()
+                .join("|");
+            return Ok(Some(Matcher::Pattern(Regex::new(&re)?)));
+        }
+
+        #[cfg(not(feature = "_regex"))]
+        let _ = patterns;
+
+        Ok(Some(Matcher::Exact(lines)))
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Self::Exact(set) => set.contains(value),
+            #[cfg(all(feature = "regex-basic", not(feature = "regex-fancy")))]
+            Self::Pattern(re) => re.is_match(value),
+            #[cfg(feature = "regex-fancy")]
+            Self::Pattern(re) => re.is_match(value).unwrap_or(false),
+        }
+    }
+}
+
+pub struct KeyFilter {
+    exclude: Option<Matcher>,
+    include: Option<Matcher>,
+}
+
+impl KeyFilter {
+    pub fn new(args: &FreqArgs) -> Result<Option<Self>, FatalError> {
+        #[cfg(feature = "_regex")]
+        let patterns = args.list_regex;
+        #[cfg(not(feature = "_regex"))]
+        let patterns = false;
+
+        let exclude = Matcher::load(&args.exclude_file, patterns)?;
+        let include = Matcher::load(&args.include_file, patterns)?;
+
+        if exclude.is_none() && include.is_none() {
+            Ok(None)
+        } else {
+            Ok(Some(KeyFilter { exclude, include }))
+        }
+    }
+
+    pub fn keep(&self, value: &str) -> bool {
+        self.exclude.as_ref().is_none_or(|m| !m.matches(value))
+            && self.include.as_ref().is_none_or(|m| m.matches(value))
+    }
+}
@@ -9,6 +9,40 @@ mod egg;
 
 mod ordered;
 
+mod crosstab;
+
+mod output;
+
+mod bins;
+
+mod normalize;
+
+mod filter;
+
+mod extract;
+
+mod rollup;
+
+mod tokens;
+
+mod sample;
+
+mod merge;
+
+mod state;
+
+mod diff;
+
+mod stats;
+
+mod sort;
+
+#[cfg(feature = "_regex")]
+mod template;
+
+#[cfg(feature = "time")]
+mod time;
+
 mod build_features;
 use build_features::*;
 
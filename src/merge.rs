@@ -0,0 +1,230 @@
+// read counts back from previous output of freq, or of `uniq -c`
+
+fn is_number(s: &str) -> bool {
+    let s = s.strip_prefix('±').unwrap_or(s);
+    !s.is_empty() && s.parse::<f64>().is_ok()
+}
+
+fn is_int(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+// the inverse of `csv_escape`
+fn csv_unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                out.push(c);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// numeric columns and value of each line
+type Rows = Vec<(Vec<String>, String)>;
+
+// the number of digits shown if the numbers in column `k` could be percentages
+// of those in column `count`, allowing for rounding
+fn pct_digits(rows: &Rows, count: usize, k: usize) -> Option<usize> {
+    let digits = rows.iter()
+        .map(|(cols, _)| cols.get(k).map_or(0, |col| col.split_once('.').map_or(0, |(_, frac)| frac.len())))
+        .max()?;
+    let half = 0.5 / 10_f64.powi(digits as i32);
+
+    // the ratio of percent to count is the same for every row
+    let (mut low, mut high, mut sum) = (0.0_f64, f64::INFINITY, 0.0);
+    for (cols, _) in rows.iter() {
+        let n = cols[count].parse::<f64>().ok()?;
+        let pct = cols.get(k)?.parse::<f64>().ok()?;
+        sum += pct;
+        if n > 0.0 {
+            low = low.max((pct - half) / n);
+            high = high.min((pct + half) / n);
+        }
+    }
+
+    // and unlike the CDF, percentages can't add up to more than 100
+    if low <= high && sum <= 100.0 + half * rows.len() as f64 { Some(digits) } else { None }
+}
+
+// the column with the counts, which comes after line numbers with `-n`, and
+// is told apart from them by which one the pct column matches more precisely
+fn count_column(rows: &Rows) -> Result<usize, String> {
+    let all_int = |k: usize| rows.iter().all(|(cols, _)| cols.get(k).is_some_and(|col| is_int(col)));
+    if !all_int(1) {
+        return Ok(0);
+    }
+
+    let width = rows.iter().map(|(cols, _)| cols.len()).min().unwrap_or(0);
+    let pct = |count: usize| (count + 1..width).filter_map(|k| pct_digits(rows, count, k)).max();
+    let same = rows.iter().all(|(cols, _)| cols[0] == cols[1]);
+
+    match (pct(0), pct(1)) {
+        _ if same => Ok(0),
+        (Some(x), Some(y)) if x != y => Ok(if x > y { 0 } else { 1 }),
+        (Some(_), None) => Ok(0),
+        (None, Some(_)) => Ok(1),
+        // a single row is consistent either way, but only starts at 1 if numbered
+        (Some(_), Some(_)) if rows.len() == 1 && rows[0].0[0] == "1" => Ok(1),
+        _ => Err(String::from("can't tell line numbers from counts, keep the pct column or use a header")),
+    }
+}
+
+// a header row, which is only written by `--by-file`, names the columns
+fn header_column(names: &[String]) -> Option<(usize, usize)> {
+    let count = names.iter().position(|name| name == "count")?;
+    let value = names.iter().position(|name| name == "value")?;
+    Some((count, value))
+}
+
+fn parse_csv(lines: &[&str]) -> Option<Rows> {
+    lines.iter().map(|line| {
+        let quote = line.find('"')?;
+        if !line.ends_with('"') || line.len() < quote + 2 {
+            return None;
+        }
+
+        let cols = line[..quote].split(',')
+            .filter(|col| !col.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        if !cols.iter().all(|col| is_number(col)) {
+            return None;
+        }
+
+        Some((cols, csv_unescape(&line[quote + 1..line.len() - 1])))
+    }).collect()
+}
+
+fn parse_tsv(lines: &[&str], value: Option<usize>) -> Option<Rows> {
+    let fields = lines.iter().map(|line| line.split('\t').collect::<Vec<_>>()).collect::<Vec<_>>();
+
+    // values can contain tabs too, so without a header the value starts after
+    // the fewest numeric columns found on any line
+    let k = match value {
+        Some(k) => k,
+        None => fields.iter()
+            .map(|fields| fields.iter().take_while(|field| is_number(field)).count().min(fields.len() - 1))
+            .min()?,
+    };
+
+    if k == 0 || fields.iter().any(|fields| fields.len() <= k || !fields[..k].iter().all(|field| is_number(field))) {
+        return None;
+    }
+
+    Some(fields.into_iter()
+        .map(|fields| (fields[..k].iter().map(|col| col.to_string()).collect(), fields[k..].join("\t")))
+        .collect())
+}
+
+// offsets where a value could start, after two spaces and only numbers
+fn value_offsets(line: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut rest = line;
+
+    loop {
+        let spaces = rest.len() - rest.trim_start_matches(' ').len();
+        let start = line.len() - rest.len() + spaces;
+        rest = &rest[spaces..];
+
+        if rest.is_empty() {
+            break;
+        }
+
+        if spaces >= 2 && start > spaces {
+            offsets.push(start);
+        }
+
+        let end = rest.find(' ').unwrap_or(rest.len());
+        if !is_number(&rest[..end]) {
+            break;
+        }
+        rest = &rest[end..];
+    }
+
+    offsets
+}
+
+fn parse_plain(lines: &[&str]) -> Option<Rows> {
+    // columns are padded to a fixed width, so the value starts at the same
+    // offset on every line, the last one that works for all of them
+    let mut offsets = value_offsets(lines.first()?);
+    for line in lines.iter().skip(1) {
+        let valid = value_offsets(line);
+        offsets.retain(|offset| valid.contains(offset));
+    }
+
+    let offset = *offsets.last()?;
+    Some(lines.iter()
+        .map(|line| (line[..offset].split_whitespace().map(String::from).collect(), line[offset..].to_string()))
+        .collect())
+}
+
+fn parse_uniq(lines: &[&str]) -> Option<Rows> {
+    lines.iter().map(|line| {
+        let (count, value) = line.trim_start_matches(' ').split_once(' ')?;
+        if is_int(count) { Some((vec![count.to_string()], value.to_string())) } else { None }
+    }).collect()
+}
+
+// detect the layout of the lines and return each value with its count
+pub fn parse(lines: &[String]) -> Result<Vec<(usize, String)>, String> {
+    let mut lines = lines.iter().map(String::as_str).filter(|line| !line.is_empty()).collect::<Vec<_>>();
+    let Some(first) = lines.first() else {
+        return Ok(Vec::new());
+    };
+
+    let mut header = None;
+    // the standard layout names the columns, but not where the value starts
+    let mut plain_count = None;
+    let rows = if first.starts_with('"') || (first.ends_with('"') && first.contains(",\"")) {
+        if first.starts_with("\"n\"") || first.starts_with("\"count\"") {
+            let names = first.split("\",\"")
+                .map(|name| csv_unescape(name.trim_matches('"')))
+                .collect::<Vec<_>>();
+            header = header_column(&names);
+            lines.remove(0);
+        }
+        parse_csv(&lines)
+    } else if first.contains('\t') && !first.starts_with(' ') {
+        if first.starts_with("n\t") || first.starts_with("count\t") {
+            let names = first.split('\t').map(String::from).collect::<Vec<_>>();
+            header = header_column(&names);
+            lines.remove(0);
+        }
+        parse_tsv(&lines, header.map(|(_, value)| value))
+    } else {
+        // labels can have spaces, so only the leading columns are known
+        let name = first.split_whitespace().next();
+        if first.ends_with("  value") && (name == Some("n") || name == Some("count")) {
+            plain_count = Some(if name == Some("n") { 1 } else { 0 });
+            lines.remove(0);
+        }
+        parse_plain(&lines).or_else(|| parse_uniq(&lines))
+    }.ok_or_else(|| String::from("unrecognized layout, expected output of freq or `uniq -c`"))?;
+
+    let count = match header {
+        Some((count, value)) if rows.iter().all(|(cols, _)| cols.len() == value) => count,
+        Some(_) => return Err(String::from("header doesn't match the columns")),
+        None => match plain_count {
+            Some(count) => count,
+            None => count_column(&rows)?,
+        },
+    };
+
+    rows.into_iter().map(|(cols, value)| {
+        let n = cols.get(count)
+            .and_then(|col| col.parse::<usize>().ok())
+            .ok_or_else(|| format!("invalid count for value `{}`", value))?;
+        Ok((n, value))
+    })
+    // values that weren't seen don't add anything
+    .filter(|row| !matches!(row, Ok((0, _))))
+    .collect()
+}
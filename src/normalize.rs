@@ -0,0 +1,109 @@
+use crate::command::FreqArgs;
+#[cfg(feature = "unicode")]
+use crate::command::Form;
+
+#[cfg(feature = "unicode")]
+use caseless::default_case_fold_str;
+#[cfg(feature = "unicode")]
+use unicode_normalization::UnicodeNormalization;
+
+pub struct Normalizer {
+    trim: bool,
+    squeeze: bool,
+    skip_empty: bool,
+    #[cfg(feature = "unicode")]
+    form: Option<Form>,
+    #[cfg(feature = "unicode")]
+    fold_case: bool,
+}
+
+impl Normalizer {
+    pub fn new(args: &FreqArgs) -> Option<Self> {
+        let norm = Normalizer {
+            trim: args.trim,
+            squeeze: args.squeeze,
+            skip_empty: args.skip_empty,
+            #[cfg(feature = "unicode")]
+            form: args.normalize,
+            #[cfg(feature = "unicode")]
+            fold_case: args.fold_case,
+        };
+
+        if norm.is_noop() { None } else { Some(norm) }
+    }
+
+    #[cfg(feature = "unicode")]
+    fn is_noop(&self) -> bool {
+        !(self.trim || self.squeeze || self.skip_empty || self.fold_case || self.form.is_some())
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    fn is_noop(&self) -> bool {
+        !(self.trim || self.squeeze || self.skip_empty)
+    }
+
+    // everything except case folding, returns `None` if the value is skipped
+    pub fn shape(&self, value: &str) -> Option<String> {
+        let parts = value.split('\t').map(|part| {
+            let part = if self.trim { part.trim() } else { part };
+            if self.squeeze {
+                let mut squeezed = String::with_capacity(part.len());
+                let mut space = false;
+                for c in part.chars() {
+                    if !c.is_whitespace() {
+                        squeezed.push(c);
+                        space = false;
+                    } else if !space {
+                        squeezed.push(' ');
+                        space = true;
+                    }
+                }
+                squeezed
+            } else {
+                part.to_string()
+            }
+        }).collect::<Vec<_>>();
+
+        if self.skip_empty && parts.iter().all(|part| part.is_empty()) {
+            return None;
+        }
+
+        let value = parts.join("\t");
+
+        #[cfg(feature = "unicode")]
+        if let Some(form) = self.form {
+            return Some(normalize(&value, form));
+        }
+
+        Some(value)
+    }
+
+    #[cfg(feature = "unicode")]
+    pub fn fold(&self, value: &str) -> Option<String> {
+        if !self.fold_case {
+            return None;
+        }
+
+        // folding can produce unnormalized text
+        let folded = default_case_fold_str(value);
+        Some(match self.form {
+            Some(form) => normalize(&folded, form),
+            None => folded,
+        })
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    pub fn fold(&self, _value: &str) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(feature = "unicode")]
+fn normalize(value: &str, form: Form) -> String {
+    match form {
+        Form::Nfc => value.nfc().collect(),
+        Form::Nfd => value.nfd().collect(),
+        Form::Nfkc => value.nfkc().collect(),
+        Form::Nfkd => value.nfkd().collect(),
+    }
+}
@@ -2,12 +2,17 @@ use std::cmp::Ordering::{self, Equal};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
+#[derive(Clone, Debug)]
 pub struct Ordered<T, U>(T, U);
 
 impl<T, U> Ordered<T, U> {
     pub fn new(n: T, s: U) -> Ordered<T, U> {
         Ordered(n, s)
     }
+
+    pub fn order(&self) -> &T {
+        &self.0
+    }
 }
 
 impl<T, U: Hash> Hash for Ordered<T, U> {
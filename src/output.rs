@@ -0,0 +1,14 @@
+// how tables are written, shared by the different kinds of output
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Standard,
+    Tsv,
+    Csv,
+}
+
+pub fn csv_escape(v: &str) -> String {
+    v.replace("\\", "\\\\")
+        .replace(",", "\\,")
+        .replace("\"", "\\\"")
+}
@@ -0,0 +1,108 @@
+use crate::command::{FreqArgs, Rollup};
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// collapse values to a prefix, like an address to its network
+pub struct Rollups {
+    ipv4: Option<u8>,
+    ipv6: Option<u8>,
+    domain: bool,
+    path: Option<usize>,
+}
+
+impl Rollups {
+    pub fn new(args: &FreqArgs) -> Option<Self> {
+        if args.rollup.is_empty() {
+            return None;
+        }
+
+        // later specs of the same kind win
+        let mut rollups = Rollups { ipv4: None, ipv6: None, domain: false, path: None };
+        for rollup in args.rollup.iter() {
+            match *rollup {
+                Rollup::Ipv4(n) => rollups.ipv4 = Some(n),
+                Rollup::Ipv6(n) => rollups.ipv6 = Some(n),
+                Rollup::Domain => rollups.domain = true,
+                Rollup::Path(n) => rollups.path = Some(n),
+            }
+        }
+
+        Some(rollups)
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        value.split('\t').map(|part| self.part(part)).collect::<Vec<_>>().join("\t")
+    }
+
+    fn part(&self, part: &str) -> String {
+        let trimmed = part.trim();
+
+        if let Some(n) = self.ipv4 {
+            if let Ok(addr) = trimmed.parse::<Ipv4Addr>() {
+                let mask = u32::MAX.checked_shl(32 - u32::from(n)).unwrap_or(0);
+                return format!("{}/{}", Ipv4Addr::from(u32::from(addr) & mask), n);
+            }
+        }
+
+        if let Some(n) = self.ipv6 {
+            if let Ok(addr) = trimmed.parse::<Ipv6Addr>() {
+                let mask = u128::MAX.checked_shl(128 - u32::from(n)).unwrap_or(0);
+                return format!("{}/{}", Ipv6Addr::from(u128::from(addr) & mask), n);
+            }
+        }
+
+        if let Some(n) = self.path {
+            if let Some(rolled) = path(trimmed, n) {
+                return rolled;
+            }
+        }
+
+        #[cfg(feature = "psl")]
+        if self.domain {
+            if let Some(rolled) = domain(trimmed) {
+                return rolled;
+            }
+        }
+
+        part.to_string()
+    }
+}
+
+// keep the first `n` segments of a URL or absolute path
+fn path(s: &str, n: usize) -> Option<String> {
+    let (base, path) = if let Some(k) = s.find("://") {
+        let rest = &s[k + 3..];
+        let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        s.split_at(k + 3 + end)
+    } else if s.starts_with('/') {
+        ("", s)
+    } else {
+        return None;
+    };
+
+    let path = &path[..path.find(['?', '#']).unwrap_or(path.len())];
+    let segments = path.split('/').filter(|segment| !segment.is_empty()).take(n);
+
+    let mut out = base.to_string();
+    for segment in segments {
+        out.push('/');
+        out.push_str(segment);
+    }
+
+    if out.len() == base.len() {
+        out.push('/');
+    }
+
+    Some(out)
+}
+
+// the registrable domain, one label below the public suffix
+#[cfg(feature = "psl")]
+fn domain(s: &str) -> Option<String> {
+    let s = s.trim_end_matches('.').to_ascii_lowercase();
+    if !s.contains('.') || !s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-') {
+        return None;
+    }
+
+    psl::domain_str(&s).map(str::to_string)
+}
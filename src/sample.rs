@@ -0,0 +1,52 @@
+use crate::command::{FreqArgs, SampleBy};
+
+use std::cell::Cell;
+use std::hash::{BuildHasher, RandomState};
+
+pub struct Sampler {
+    by: SampleBy,
+    seed: u64,
+    state: Cell<u64>,
+    // sampled if a uniform random 64 bit value is below this
+    threshold: u64,
+}
+
+// SplitMix64, small and good enough to pick lines
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Sampler {
+    pub fn new(args: &FreqArgs) -> Option<Self> {
+        let rate = args.sample?;
+        let seed = args.seed.unwrap_or_else(|| RandomState::new().hash_one(0u64));
+
+        let threshold = if rate >= 1.0 { u64::MAX } else { (rate * u64::MAX as f64) as u64 };
+        Some(Sampler { by: args.sample_by, seed, state: Cell::new(seed), threshold })
+    }
+
+    pub fn keep_line(&self) -> bool {
+        if self.by != SampleBy::Line {
+            return true;
+        }
+
+        let state = self.state.get().wrapping_add(0x9e3779b97f4a7c15);
+        self.state.set(state);
+        mix(state) <= self.threshold
+    }
+
+    // the same value is always either kept or skipped for a given seed
+    pub fn keep_key(&self, key: &str) -> bool {
+        if self.by != SampleBy::Key {
+            return true;
+        }
+
+        // FNV-1a, then mixed so that similar values don't hash similarly
+        let hash = key.bytes().fold(0xcbf29ce484222325 ^ self.seed, |h, b| {
+            (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+        });
+        mix(hash) <= self.threshold
+    }
+}
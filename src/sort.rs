@@ -0,0 +1,108 @@
+// comparisons for `--sort` keys
+
+use crate::command::{Collation, SortField, SortKey};
+use crate::ordered::OrderedString;
+
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+// compares runs of digits by their numeric value, and everything else by bytes
+fn natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, rest_a) = a.split_at(a.iter().position(|c| !c.is_ascii_digit()).unwrap_or(a.len()));
+                let (y, rest_b) = b.split_at(b.iter().position(|c| !c.is_ascii_digit()).unwrap_or(b.len()));
+                (a, b) = (rest_a, rest_b);
+
+                // without leading zeros, a longer run is a bigger number
+                let x_digits = &x[x.iter().position(|c| *c != b'0').unwrap_or(x.len())..];
+                let y_digits = &y[y.iter().position(|c| *c != b'0').unwrap_or(y.len())..];
+                let ord = x_digits.len().cmp(&y_digits.len())
+                    .then_with(|| x_digits.cmp(y_digits))
+                    // then fewer leading zeros first
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            },
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            },
+        }
+    }
+}
+
+// values that aren't numbers sort after those that are
+fn numeric(a: &str, b: &str) -> Ordering {
+    let parse = |s: &str| s.trim().parse::<f64>().ok().filter(|v| !v.is_nan());
+    match (parse(a), parse(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// addresses, optionally with a prefix length like the ones from `--rollup`
+fn parse_ip(s: &str) -> Option<(u8, u128, u8)> {
+    let (addr, prefix) = match s.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().ok()?)),
+        None => (s, None),
+    };
+
+    match addr.parse::<IpAddr>().ok()? {
+        IpAddr::V4(ip) => Some((4, u32::from(ip).into(), prefix.unwrap_or(32))),
+        IpAddr::V6(ip) => Some((6, u128::from(ip), prefix.unwrap_or(128))),
+    }
+}
+
+// IPv4 before IPv6, then by address, then wider networks first
+fn ip(a: &str, b: &str) -> Ordering {
+    match (parse_ip(a), parse_ip(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn nocase(a: &str, b: &str) -> Ordering {
+    a.chars().flat_map(char::to_lowercase).cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+fn collate(collation: Collation, a: &str, b: &str) -> Ordering {
+    match collation {
+        Collation::Bytes => a.cmp(b),
+        Collation::Natural => natural(a, b),
+        Collation::Numeric => numeric(a, b),
+        Collation::Ip => ip(a, b),
+        Collation::Length => a.chars().count().cmp(&b.chars().count()),
+        Collation::Nocase => nocase(a, b),
+    }
+}
+
+// compare by each key in turn, until one of them differs
+pub fn compare(keys: &[SortKey], (a, a_n): &(OrderedString, usize), (b, b_n): &(OrderedString, usize)) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ord = match key.field {
+                SortField::Count => a_n.cmp(b_n),
+                SortField::Value(collation) => collate(collation, a.as_ref(), b.as_ref()),
+                // values seen on the same line share an order, so the value
+                // itself breaks the tie
+                SortField::Order => a.cmp(b),
+            };
+            if key.desc { ord.reverse() } else { ord }
+        })
+        .find(|ord| *ord != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
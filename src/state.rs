@@ -0,0 +1,131 @@
+// compact binary snapshots of a counter, for accumulating counts across runs
+//
+// layout, with integers as unsigned LEB128:
+//   magic `FRQS`, version byte, flags byte, number of keys, total count,
+//   then for each key: first-seen order, count, length, UTF-8 bytes
+
+use crate::command::FatalError;
+use crate::ordered::OrderedString;
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use counter::Counter;
+
+const MAGIC: &[u8; 4] = b"FRQS";
+const VERSION: u8 = 1;
+
+// values are prefixed with a time bucket
+const FLAG_SERIES: u8 = 1;
+
+fn write_uint<W: Write>(out: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_uint<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        input.read_exact(&mut byte)?;
+        n |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "integer too long"))
+}
+
+fn read_usize<R: Read>(input: &mut R) -> io::Result<usize> {
+    usize::try_from(read_uint(input)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "integer too large"))
+}
+
+pub fn save(path: &str, force: bool, counter: &Counter<OrderedString>, series: bool) -> Result<(), FatalError> {
+    let file = File::options()
+        .write(true)
+        .truncate(true)
+        .create(force)
+        .create_new(!force)
+        .open(path)
+        .map_err(|e| FatalError::new(1, format!("Error opening `{}`: {}", path, e)))?;
+    let mut out = BufWriter::new(file);
+
+    out.write_all(MAGIC)?;
+    out.write_all(&[VERSION, if series { FLAG_SERIES } else { 0 }])?;
+    write_uint(&mut out, counter.len() as u64)?;
+    write_uint(&mut out, counter.total::<usize>() as u64)?;
+
+    for (value, count) in counter.iter() {
+        write_uint(&mut out, *value.order() as u64)?;
+        write_uint(&mut out, *count as u64)?;
+        write_uint(&mut out, value.len() as u64)?;
+        out.write_all(value.as_bytes())?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+// whether a file starts like a state file
+pub fn detect(path: &str) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == MAGIC
+}
+
+pub fn load(path: &str, series: bool) -> Result<Counter<OrderedString>, FatalError> {
+    let error = |msg: &str| FatalError::new(1, format!("Error loading state from `{}`: {}", path, msg));
+
+    let file = File::open(path)
+        .map_err(|e| FatalError::new(1, format!("Error opening `{}`: {}", path, e)))?;
+    let mut input = BufReader::new(file);
+
+    let mut header = [0u8; 6];
+    input.read_exact(&mut header).map_err(|_| error("not a state file"))?;
+    if &header[..4] != MAGIC {
+        return Err(error("not a state file"));
+    } else if header[4] != VERSION {
+        return Err(error(&format!("unsupported version {}", header[4])));
+    } else if (header[5] & FLAG_SERIES != 0) != series {
+        return Err(error("counts over time can't be combined with plain counts"));
+    }
+
+    let read = |input: &mut BufReader<File>| -> io::Result<(usize, Counter<OrderedString>)> {
+        let len = read_usize(input)?;
+        let total = read_usize(input)?;
+
+        let mut counter = Counter::new();
+        for _ in 0..len {
+            let order = read_usize(input)?;
+            let count = read_usize(input)?;
+
+            // don't trust the length enough to allocate it up front
+            let len = read_uint(input)?;
+            let mut bytes = Vec::new();
+            input.by_ref().take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let value = String::from_utf8(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            counter.insert(OrderedString::new(order, value), count);
+        }
+
+        Ok((total, counter))
+    };
+
+    let (total, counter) = read(&mut input).map_err(|e| error(&e.to_string()))?;
+    if counter.total::<usize>() != total {
+        return Err(error("counts don't add up to the total"));
+    }
+
+    Ok(counter)
+}
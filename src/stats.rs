@@ -0,0 +1,247 @@
+// statistical tests, with the special functions they need
+
+use crate::command::Test;
+
+// natural log of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEF: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEF.iter().enumerate().skip(1).fold(COEF[0], |sum, (i, c)| sum + c / (x + i as f64));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// regularized upper incomplete gamma function Q(a, x)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPS: f64 = 1e-14;
+    const ITERATIONS: usize = 500;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let front = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // series for P(a, x), which converges quickly here
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..ITERATIONS {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPS {
+                break;
+            }
+        }
+        (1.0 - sum * front).clamp(0.0, 1.0)
+    } else {
+        // continued fraction for Q(a, x), using the modified Lentz method
+        let tiny = f64::MIN_POSITIVE / EPS;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..=ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny { d = tiny; }
+            c = b + an / c;
+            if c.abs() < tiny { c = tiny; }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPS {
+                break;
+            }
+        }
+        (front * h).clamp(0.0, 1.0)
+    }
+}
+
+// probability of a chi-square statistic at least this large
+pub fn chi2_p(stat: f64, df: usize) -> f64 {
+    if df == 0 {
+        1.0
+    } else {
+        gamma_q(df as f64 / 2.0, stat / 2.0)
+    }
+}
+
+// quantile of the standard normal distribution, using Acklam's rational
+// approximation, which is accurate to about 1e-9
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2, 1.38357751867269e2, -3.066479806614716e1, 2.506628277459239];
+    const B: [f64; 5] = [-5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2, 6.680131188771972e1, -1.328068155288572e1];
+    const C: [f64; 6] = [-7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838, -2.549732539343734, 4.374664141464968, 2.938163982698783];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416];
+    const LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+
+    if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+// Wilson score interval for a proportion of `k` out of `n`, where `z` is the
+// normal quantile for the confidence level
+pub fn wilson(k: f64, n: f64, z: f64) -> (f64, f64) {
+    if n <= 0.0 {
+        return (0.0, 1.0);
+    }
+
+    let p = k / n;
+    let z2 = z * z;
+    let denom = 1.0 + z2 / n;
+    let center = (p + z2 / (2.0 * n)) / denom;
+    let half = z / denom * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+// test whether the rows of a table of counts come from the same distribution,
+// returns the statistic and degrees of freedom
+pub fn homogeneity(test: Test, rows: &[&[usize]]) -> (f64, usize) {
+    let cols = rows.first().map_or(0, |row| row.len());
+    let row_totals = rows.iter().map(|row| row.iter().sum::<usize>() as f64).collect::<Vec<_>>();
+    let col_totals = (0..cols)
+        .map(|j| rows.iter().map(|row| row[j]).sum::<usize>() as f64)
+        .collect::<Vec<_>>();
+    let total = row_totals.iter().sum::<f64>();
+
+    // empty rows and columns don't count towards the degrees of freedom
+    let nonzero = |totals: &[f64]| totals.iter().filter(|t| **t > 0.0).count();
+    let df = nonzero(&row_totals).saturating_sub(1) * nonzero(&col_totals).saturating_sub(1);
+    if df == 0 {
+        return (0.0, 0);
+    }
+
+    let mut stat = 0.0;
+    for (row, row_total) in rows.iter().zip(row_totals.iter()) {
+        for (n, col_total) in row.iter().zip(col_totals.iter()) {
+            let expected = row_total * col_total / total;
+            let observed = *n as f64;
+            if expected > 0.0 {
+                stat += match test {
+                    Test::G2 if observed > 0.0 => 2.0 * observed * (observed / expected).ln(),
+                    Test::G2 => 0.0,
+                    Test::Chi2 => (observed - expected).powi(2) / expected,
+                };
+            }
+        }
+    }
+
+    (stat.max(0.0), df)
+}
+
+// summary of a distribution of counts, one per distinct value
+pub struct Summary {
+    pub total: usize,
+    pub distinct: usize,
+    pub singletons: usize,
+    // Shannon entropy in bits, and as a fraction of the maximum possible
+    pub entropy: f64,
+    pub normalized_entropy: f64,
+    pub gini: f64,
+    // slope of log count against log rank, if there's enough data to fit
+    pub zipf: Option<f64>,
+    // total count of the most common values
+    pub top: usize,
+    pub median: f64,
+    pub mean: f64,
+}
+
+impl Summary {
+    pub fn new(counts: &[usize], top_n: usize) -> Self {
+        let mut counts = counts.to_vec();
+        counts.sort_unstable();
+
+        let n = counts.len();
+        let total = counts.iter().sum::<usize>();
+        let total_f = total as f64;
+
+        let entropy = -counts.iter()
+            .filter(|c| **c > 0)
+            .map(|c| *c as f64 / total_f)
+            .map(|p| p * p.log2())
+            .sum::<f64>();
+        let normalized_entropy = if n > 1 { entropy / (n as f64).log2() } else { 0.0 };
+
+        // with counts in ascending order
+        let gini = if n > 0 && total > 0 {
+            counts.iter()
+                .enumerate()
+                .map(|(i, c)| (2.0 * (i + 1) as f64 - n as f64 - 1.0) * *c as f64)
+                .sum::<f64>() / (n as f64 * total_f)
+        } else {
+            0.0
+        };
+
+        // least squares fit over the ranks, from the most common value down
+        let points = counts.iter().rev()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(rank, c)| (((rank + 1) as f64).ln(), (*c as f64).ln()))
+            .collect::<Vec<_>>();
+        let zipf = if points.len() >= 2 {
+            let m = points.len() as f64;
+            let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / m;
+            let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / m;
+            let cov = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum::<f64>();
+            let var = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum::<f64>();
+            // subtracting from zero avoids showing -0 for flat distributions
+            Some(0.0 - cov / var)
+        } else {
+            None
+        };
+
+        let median = match n {
+            0 => 0.0,
+            _ if n % 2 == 1 => counts[n / 2] as f64,
+            _ => (counts[n / 2 - 1] + counts[n / 2]) as f64 / 2.0,
+        };
+
+        Summary {
+            total,
+            distinct: n,
+            singletons: counts.iter().filter(|c| **c == 1).count(),
+            entropy,
+            normalized_entropy,
+            gini,
+            zipf,
+            top: counts.iter().rev().take(top_n).sum(),
+            median,
+            mean: if n > 0 { total_f / n as f64 } else { 0.0 },
+        }
+    }
+}
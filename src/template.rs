@@ -0,0 +1,154 @@
+// templates for building values from capture groups, like `$host/${path|lower}`
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ref {
+    Name(String),
+    Number(usize),
+}
+
+impl fmt::Display for Ref {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "${}", name),
+            Self::Number(num) => write!(f, "${}", num),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Transform {
+    Lower,
+    Upper,
+    Trim,
+    Truncate(usize),
+}
+
+#[derive(Debug)]
+enum Piece {
+    Literal(String),
+    Group(Ref, Vec<Transform>),
+}
+
+#[derive(Debug)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+fn parse_ref(s: &str) -> Result<Ref, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Err(format!("invalid capture group reference `{}`", s))
+    } else if let Ok(n) = s.parse() {
+        Ok(Ref::Number(n))
+    } else {
+        Ok(Ref::Name(s.to_string()))
+    }
+}
+
+fn parse_transform(s: &str) -> Result<Transform, String> {
+    let (name, arg) = match s.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (s, None),
+    };
+
+    match (name, arg) {
+        ("lower", None) => Ok(Transform::Lower),
+        ("upper", None) => Ok(Transform::Upper),
+        ("trim", None) => Ok(Transform::Trim),
+        ("truncate", Some(n)) => n.parse()
+            .map(Transform::Truncate)
+            .map_err(|_| format!("invalid length `{}` for truncate", n)),
+        _ => Err(format!("unknown transform `{}`, expected lower, upper, trim, or truncate:N", s)),
+    }
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+
+        while let Some(k) = rest.find('$') {
+            literal.push_str(&rest[..k]);
+            rest = &rest[k + 1..];
+
+            let group = if let Some(inner) = rest.strip_prefix('{') {
+                // `${group|transform|...}`
+                let end = inner.find('}')
+                    .ok_or_else(|| format!("unclosed `${{` in template `{}`", s))?;
+                let mut parts = inner[..end].split('|');
+                let group = parse_ref(parts.next().unwrap_or(""))?;
+                let transforms = parts.map(parse_transform).collect::<Result<Vec<_>, _>>()?;
+                rest = &inner[end + 1..];
+                Some((group, transforms))
+            } else {
+                // `$name` or `$1`, where numbers end at the first non-digit
+                let end = if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                    rest.find(|c: char| !c.is_ascii_digit())
+                } else {
+                    rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                }.unwrap_or(rest.len());
+
+                if end == 0 {
+                    // `$$` or a `$` that isn't followed by a group
+                    if rest.starts_with('$') {
+                        rest = &rest[1..];
+                    }
+                    literal.push('$');
+                    None
+                } else {
+                    let group = parse_ref(&rest[..end])?;
+                    rest = &rest[end..];
+                    Some((group, Vec::new()))
+                }
+            };
+
+            if let Some((group, transforms)) = group {
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Group(group, transforms));
+            }
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        Ok(Template { pieces })
+    }
+
+    pub fn refs(&self) -> impl Iterator<Item = &Ref> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Group(group, _) => Some(group),
+            Piece::Literal(_) => None,
+        })
+    }
+
+    // groups that didn't participate in the match expand to nothing
+    pub fn expand<'a, F: Fn(&Ref) -> Option<&'a str>>(&self, get: F) -> String {
+        let mut out = String::new();
+
+        for piece in self.pieces.iter() {
+            match piece {
+                Piece::Literal(s) => out.push_str(s),
+                Piece::Group(group, transforms) => {
+                    let mut value = get(group).unwrap_or("").to_string();
+                    for transform in transforms.iter() {
+                        value = match *transform {
+                            Transform::Lower => value.to_lowercase(),
+                            Transform::Upper => value.to_uppercase(),
+                            Transform::Trim => value.trim().to_string(),
+                            Transform::Truncate(n) => value.chars().take(n).collect(),
+                        };
+                    }
+                    out.push_str(&value);
+                },
+            }
+        }
+
+        out
+    }
+}
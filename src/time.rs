@@ -0,0 +1,135 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+
+// formats tried in order when no format is given, after epoch timestamps
+const ZONED_FORMATS: &[&str] = &[
+    // RFC 3339 / ISO 8601
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    // Apache / nginx common log format
+    "%d/%b/%Y:%H:%M:%S %z",
+];
+
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+];
+
+pub struct TimeBucket {
+    format: Option<String>,
+    width: i64,
+}
+
+impl TimeBucket {
+    pub fn new(format: Option<String>, width: i64) -> Self {
+        TimeBucket { format, width }
+    }
+
+    // parse a timestamp, returning the start of its bucket in unix seconds
+    pub fn bucket(&self, s: &str) -> Option<i64> {
+        let s = s.trim().trim_matches(|c| c == '[' || c == ']' || c == '"');
+
+        let t = match self.format {
+            Some(ref format) => parse_with(s, format),
+            None => parse_auto(s),
+        }?;
+
+        Some(t - t.rem_euclid(self.width))
+    }
+}
+
+fn parse_with(s: &str, format: &str) -> Option<i64> {
+    if let Ok(t) = DateTime::parse_from_str(s, format) {
+        Some(t.timestamp())
+    } else {
+        // assume UTC if there's no offset
+        NaiveDateTime::parse_from_str(s, format).ok().map(|t| t.and_utc().timestamp())
+    }
+}
+
+fn parse_auto(s: &str) -> Option<i64> {
+    if let Some(t) = parse_epoch(s) {
+        return Some(t);
+    }
+
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.timestamp());
+    }
+
+    ZONED_FORMATS.iter()
+        .find_map(|format| DateTime::parse_from_str(s, format).ok())
+        .map(|t| t.timestamp())
+        .or_else(|| NAIVE_FORMATS.iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+            .map(|t| t.and_utc().timestamp()))
+}
+
+fn parse_epoch(s: &str) -> Option<i64> {
+    let whole = s.split_once('.').map_or(s, |(whole, _)| whole);
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let n: i64 = whole.parse().ok()?;
+
+    // anything past 5138 AD in seconds is taken to be milliseconds, and small
+    // numbers before 1973 are more likely counts or status codes than times
+    match n {
+        100_000_000..=99_999_999_999 => Some(n),
+        100_000_000_000..=99_999_999_999_999 => Some(n.div_euclid(1000)),
+        _ => None,
+    }
+}
+
+pub fn format_bucket(t: &str) -> String {
+    t.parse::<i64>().ok()
+        .and_then(|t| DateTime::from_timestamp(t, 0))
+        .map_or_else(|| t.to_string(), |t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+// split out a whitespace separated field, treating text enclosed in square
+// brackets or double quotes as a single field, returns the field and the
+// remainder of the line
+pub fn split_field(s: &str, n: usize) -> Option<(&str, String)> {
+    let mut fields = Vec::<(usize, usize)>::new();
+    let mut start = None;
+    let mut close = None;
+
+    for (i, c) in s.char_indices() {
+        match (start, close) {
+            (None, _) if c.is_whitespace() => (),
+            (None, _) => {
+                start = Some(i);
+                close = match c {
+                    '[' => Some(']'),
+                    '"' => Some('"'),
+                    _ => None,
+                };
+            },
+            (Some(j), Some(end)) if c == end && i > j => {
+                fields.push((j, i + c.len_utf8()));
+                start = None;
+                close = None;
+            },
+            (Some(j), None) if c.is_whitespace() => {
+                fields.push((j, i));
+                start = None;
+            },
+            _ => (),
+        }
+
+        if fields.len() == n {
+            break;
+        }
+    }
+
+    if let Some(j) = start {
+        if fields.len() < n {
+            fields.push((j, s.len()));
+        }
+    }
+
+    let (j, k) = *fields.get(n.checked_sub(1)?)?;
+    let rest = format!("{}{}", &s[..j], &s[k..]);
+    Some((&s[j..k], rest.split_whitespace().collect::<Vec<_>>().join(" ")))
+}
@@ -0,0 +1,110 @@
+use crate::command::{FatalError, FreqArgs, Tokens};
+use crate::input::Input;
+
+use std::collections::HashSet;
+use std::io::BufRead;
+
+#[cfg(feature = "unicode")]
+use unicode_segmentation::UnicodeSegmentation;
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and",
+    "any", "are", "as", "at", "be", "because", "been", "before", "being", "below",
+    "between", "both", "but", "by", "can", "could", "did", "do", "does", "doing",
+    "down", "during", "each", "few", "for", "from", "further", "had", "has", "have",
+    "having", "he", "her", "here", "hers", "herself", "him", "himself", "his", "how",
+    "i", "if", "in", "into", "is", "it", "it's", "its", "itself", "just",
+    "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of",
+    "off", "on", "once", "only", "or", "other", "our", "ours", "ourselves", "out",
+    "over", "own", "same", "she", "should", "so", "some", "such", "than", "that",
+    "the", "their", "theirs", "them", "themselves", "then", "there", "these", "they", "this",
+    "those", "through", "to", "too", "under", "until", "up", "very", "was", "we",
+    "were", "what", "when", "where", "which", "while", "who", "whom", "why", "will",
+    "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+pub struct Tokenizer {
+    mode: Tokens,
+    ngram: usize,
+    stopwords: HashSet<String>,
+}
+
+impl Tokenizer {
+    pub fn new(args: &FreqArgs) -> Result<Option<Self>, FatalError> {
+        let stopping = args.stopwords || !args.stopword_file.is_empty();
+        if args.tokens.is_none() && args.ngram.is_none() && !stopping {
+            return Ok(None);
+        }
+
+        let mut stopwords = HashSet::new();
+        if args.stopwords {
+            stopwords.extend(STOPWORDS.iter().map(|word| word.to_string()));
+        }
+
+        for path in args.stopword_file.iter() {
+            let input = Input::path(path).map_err(|e| {
+                FatalError::new(1, format!("Error opening `{}`: {}", path, e))
+            })?;
+
+            for line in input.lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    stopwords.insert(line.to_lowercase());
+                }
+            }
+        }
+
+        Ok(Some(Tokenizer {
+            mode: args.tokens.unwrap_or(Tokens::Words),
+            ngram: args.ngram.map_or(1, |n| n.get()),
+            stopwords,
+        }))
+    }
+
+    fn split(&self, line: &str) -> Vec<String> {
+        match self.mode {
+            Tokens::Words => words(line),
+            Tokens::Chars => line.chars()
+                .filter(|c| !c.is_whitespace())
+                .map(String::from)
+                .collect(),
+            #[cfg(feature = "unicode")]
+            Tokens::Graphemes => line.graphemes(true)
+                .filter(|g| !g.chars().all(char::is_whitespace))
+                .map(String::from)
+                .collect(),
+            Tokens::Bytes => line.bytes()
+                .map(|b| if b.is_ascii_graphic() { char::from(b).to_string() } else { format!("\\x{:02x}", b) })
+                .collect(),
+        }
+    }
+
+    pub fn tokens(&self, line: &str) -> Vec<String> {
+        let mut tokens = self.split(line);
+        if !self.stopwords.is_empty() {
+            tokens.retain(|token| !self.stopwords.contains(&token.to_lowercase()));
+        }
+
+        if self.ngram == 1 {
+            return tokens;
+        }
+
+        let sep = if self.mode == Tokens::Words { " " } else { "" };
+        tokens.windows(self.ngram).map(|gram| gram.join(sep)).collect()
+    }
+}
+
+#[cfg(feature = "unicode")]
+fn words(line: &str) -> Vec<String> {
+    line.unicode_words().map(String::from).collect()
+}
+
+// apostrophes are kept within words, like `don't`
+#[cfg(not(feature = "unicode"))]
+fn words(line: &str) -> Vec<String> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| word.trim_matches(|c| c == '\'' || c == '’'))
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}